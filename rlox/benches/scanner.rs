@@ -0,0 +1,45 @@
+use core::hint;
+use std::time::Instant;
+
+use rlox::scanner::{Scanner, TokenType};
+
+const LINE: &str =
+    "    total = total + 12.5 * (count - 3) / \"some text\" and !done;\n";
+
+fn source(size: usize) -> String {
+    let mut source = String::with_capacity(size + 64);
+    source.push_str("fun unused() {\n");
+    while source.len() < size {
+        source.push_str(LINE);
+    }
+    source.push_str("}\n");
+    source
+}
+
+fn main() {
+    println!(
+        "{:>8} {:>12} {:>12} {:>12}",
+        "MiB", "tokens", "time (ms)", "MiB/s"
+    );
+    for mib in [1_u16, 2, 4, 8, 16] {
+        let source = source(usize::from(mib) * 1024 * 1024);
+
+        let start = Instant::now();
+        let mut scanner = Scanner::new(hint::black_box(&source));
+        let mut tokens = 0_usize;
+        loop {
+            let token = scanner.scan_token();
+            tokens += 1;
+            if token.r#type == TokenType::Eof {
+                break;
+            }
+        }
+        let elapsed = start.elapsed();
+
+        println!(
+            "{mib:>8} {tokens:>12} {:>12.1} {:>12.1}",
+            elapsed.as_secs_f64() * 1000.0,
+            f64::from(mib) / elapsed.as_secs_f64(),
+        );
+    }
+}
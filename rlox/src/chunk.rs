@@ -1,12 +1,24 @@
 use core::fmt::{self, Debug, Formatter};
 
-use crate::value::Value;
+use crate::{scanner::Span, value::Value};
 
+#[derive(Clone, Copy)]
 pub enum OpCode {
     Constant(usize),
     Nil,
     True,
     False,
+    Pop,
+    GetLocal(usize),
+    SetLocal(usize),
+    GetGlobal(usize),
+    DefineGlobal(usize),
+    SetGlobal(usize),
+    GetUpvalue(usize),
+    SetUpvalue(usize),
+    GetProperty(usize),
+    SetProperty(usize),
+    GetSuper(usize),
     Equal,
     Greater,
     Less,
@@ -16,13 +28,26 @@ pub enum OpCode {
     Divide,
     Not,
     Negate,
+    Print,
+    Stringify,
+    Jump(usize),
+    JumpIfFalse(usize),
+    Loop(usize),
+    Call(usize),
+    Invoke(usize, usize),
+    SuperInvoke(usize, usize),
+    Closure(usize),
+    CloseUpvalue,
     Return,
+    Class(usize),
+    Inherit,
+    Method(usize),
 }
 
 pub struct Chunk {
     pub codes: Vec<OpCode>,
     pub constants: Vec<Value>,
-    pub lines: Vec<i32>,
+    pub spans: Vec<Span>,
 }
 
 impl Chunk {
@@ -30,18 +55,155 @@ impl Chunk {
         Self {
             codes: Vec::new(),
             constants: Vec::new(),
-            lines: Vec::new(),
+            spans: Vec::new(),
         }
     }
 
-    pub fn write_opcode(&mut self, code: OpCode, line: i32) {
+    pub fn write_opcode(&mut self, code: OpCode, span: Span) {
         self.codes.push(code);
-        self.lines.push(line);
+        self.spans.push(span);
     }
 
-    pub fn write_constant(&mut self, constant: Value, line: i32) {
+    pub fn add_constant(&mut self, constant: Value) -> usize {
         self.constants.push(constant);
-        self.write_opcode(OpCode::Constant(self.constants.len() - 1), line);
+        self.constants.len() - 1
+    }
+
+    pub fn write_constant(&mut self, constant: Value, span: Span) {
+        let const_idx = self.add_constant(constant);
+        self.write_opcode(OpCode::Constant(const_idx), span);
+    }
+
+    fn constant_instruction(
+        &self,
+        f: &mut Formatter<'_>,
+        name: &str,
+        const_idx: usize,
+    ) -> fmt::Result {
+        #[expect(
+            clippy::indexing_slicing,
+            reason = r#"
+                Constant indexes are always valid as they come from
+                `self.add_constant()`.
+            "#
+        )]
+        let const_val = &self.constants[const_idx];
+        writeln!(f, "{name:<16} {const_idx:4} {const_val}")
+    }
+
+    fn invoke_instruction(
+        &self,
+        f: &mut Formatter<'_>,
+        name: &str,
+        const_idx: usize,
+        arg_count: usize,
+    ) -> fmt::Result {
+        #[expect(
+            clippy::indexing_slicing,
+            reason = r#"
+                Constant indexes are always valid as they come from
+                `self.add_constant()`.
+            "#
+        )]
+        let const_val = &self.constants[const_idx];
+        writeln!(f, "{name:<16} ({arg_count} args) {const_idx:4} {const_val}")
+    }
+
+    fn disassemble_instruction(
+        &self,
+        f: &mut Formatter<'_>,
+        i: usize,
+        instruction: OpCode,
+    ) -> fmt::Result {
+        match instruction {
+            OpCode::Constant(const_idx) => {
+                self.constant_instruction(f, "OP_CONSTANT", const_idx)?;
+            }
+            OpCode::Nil => writeln!(f, "OP_NIL")?,
+            OpCode::True => writeln!(f, "OP_TRUE")?,
+            OpCode::False => writeln!(f, "OP_FALSE")?,
+            OpCode::Pop => writeln!(f, "OP_POP")?,
+            OpCode::GetLocal(slot) => {
+                writeln!(f, "{:<16} {slot:4}", "OP_GET_LOCAL")?;
+            }
+            OpCode::SetLocal(slot) => {
+                writeln!(f, "{:<16} {slot:4}", "OP_SET_LOCAL")?;
+            }
+            OpCode::GetGlobal(const_idx) => {
+                self.constant_instruction(f, "OP_GET_GLOBAL", const_idx)?;
+            }
+            OpCode::DefineGlobal(const_idx) => {
+                self.constant_instruction(f, "OP_DEFINE_GLOBAL", const_idx)?;
+            }
+            OpCode::SetGlobal(const_idx) => {
+                self.constant_instruction(f, "OP_SET_GLOBAL", const_idx)?;
+            }
+            OpCode::GetUpvalue(slot) => {
+                writeln!(f, "{:<16} {slot:4}", "OP_GET_UPVALUE")?;
+            }
+            OpCode::SetUpvalue(slot) => {
+                writeln!(f, "{:<16} {slot:4}", "OP_SET_UPVALUE")?;
+            }
+            OpCode::GetProperty(const_idx) => {
+                self.constant_instruction(f, "OP_GET_PROPERTY", const_idx)?;
+            }
+            OpCode::SetProperty(const_idx) => {
+                self.constant_instruction(f, "OP_SET_PROPERTY", const_idx)?;
+            }
+            OpCode::GetSuper(const_idx) => {
+                self.constant_instruction(f, "OP_GET_SUPER", const_idx)?;
+            }
+            OpCode::Equal => writeln!(f, "OP_EQUAL")?,
+            OpCode::Greater => writeln!(f, "OP_GREATER")?,
+            OpCode::Less => writeln!(f, "OP_LESS")?,
+            OpCode::Add => writeln!(f, "OP_ADD")?,
+            OpCode::Subtract => writeln!(f, "OP_SUBTRACT")?,
+            OpCode::Multiply => writeln!(f, "OP_MULTIPLY")?,
+            OpCode::Divide => writeln!(f, "OP_DIVIDE")?,
+            OpCode::Not => writeln!(f, "OP_NOT")?,
+            OpCode::Negate => writeln!(f, "OP_NEGATE")?,
+            OpCode::Print => writeln!(f, "OP_PRINT")?,
+            OpCode::Stringify => writeln!(f, "OP_STRINGIFY")?,
+            OpCode::Jump(offset) => {
+                let target = i + 1 + offset;
+                writeln!(f, "{:<16} {i:4} -> {target}", "OP_JUMP")?;
+            }
+            OpCode::JumpIfFalse(offset) => {
+                let target = i + 1 + offset;
+                writeln!(f, "{:<16} {i:4} -> {target}", "OP_JUMP_IF_FALSE")?;
+            }
+            OpCode::Loop(offset) => {
+                let target = i + 1 - offset;
+                writeln!(f, "{:<16} {i:4} -> {target}", "OP_LOOP")?;
+            }
+            OpCode::Call(arg_count) => {
+                writeln!(f, "{:<16} {arg_count:4}", "OP_CALL")?;
+            }
+            OpCode::Invoke(const_idx, arg_count) => {
+                self.invoke_instruction(f, "OP_INVOKE", const_idx, arg_count)?;
+            }
+            OpCode::SuperInvoke(const_idx, arg_count) => {
+                self.invoke_instruction(
+                    f,
+                    "OP_SUPER_INVOKE",
+                    const_idx,
+                    arg_count,
+                )?;
+            }
+            OpCode::Closure(const_idx) => {
+                self.constant_instruction(f, "OP_CLOSURE", const_idx)?;
+            }
+            OpCode::CloseUpvalue => writeln!(f, "OP_CLOSE_UPVALUE")?,
+            OpCode::Return => writeln!(f, "OP_RETURN")?,
+            OpCode::Class(const_idx) => {
+                self.constant_instruction(f, "OP_CLASS", const_idx)?;
+            }
+            OpCode::Inherit => writeln!(f, "OP_INHERIT")?,
+            OpCode::Method(const_idx) => {
+                self.constant_instruction(f, "OP_METHOD", const_idx)?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -54,54 +216,25 @@ impl Debug for Chunk {
             #[expect(
                 clippy::indexing_slicing,
                 reason = r#"
-                    `self.codes` and `self.lines` vectors are always modified
+                    `self.codes` and `self.spans` vectors are always modified
                     together via `self.write_opcode()`.
                 "#
             )]
-            let line = self.lines[i];
+            let line = self.spans[i].line;
             #[expect(
                 clippy::indexing_slicing,
                 reason = r#"
-                    `self.codes` and `self.lines` always have the same length
+                    `self.codes` and `self.spans` always have the same length
                     (`i`), validated through enumeration.
                 "#
             )]
-            if i > 0 && line == self.lines[i - 1] {
+            if i > 0 && line == self.spans[i - 1].line {
                 write!(f, "   | ")?;
             } else {
                 write!(f, "{line:04} ")?;
             }
 
-            match *instruction {
-                OpCode::Constant(const_idx) => {
-                    #[expect(
-                        clippy::indexing_slicing,
-                        reason = r#"
-                            Constant indexes are always valid as they come from
-                            `self.write_constant()`.
-                        "#
-                    )]
-                    let const_val = &self.constants[const_idx];
-                    writeln!(
-                        f,
-                        "{:<16} {const_idx:4} {const_val}",
-                        "OP_CONSTANT"
-                    )?;
-                }
-                OpCode::Nil => writeln!(f, "OP_NIL")?,
-                OpCode::True => writeln!(f, "OP_TRUE")?,
-                OpCode::False => writeln!(f, "OP_TRUE")?,
-                OpCode::Equal => writeln!(f, "OP_EQUAL")?,
-                OpCode::Greater => writeln!(f, "OP_GREATER")?,
-                OpCode::Less => writeln!(f, "OP_LESS")?,
-                OpCode::Add => writeln!(f, "OP_ADD")?,
-                OpCode::Subtract => writeln!(f, "OP_SUBTRACT")?,
-                OpCode::Multiply => writeln!(f, "OP_MULTIPLY")?,
-                OpCode::Divide => writeln!(f, "OP_DIVIDE")?,
-                OpCode::Not => writeln!(f, "OP_NOT")?,
-                OpCode::Negate => writeln!(f, "OP_NEGATE")?,
-                OpCode::Return => writeln!(f, "OP_RETURN")?,
-            }
+            self.disassemble_instruction(f, i, *instruction)?;
         }
         Ok(())
     }
@@ -1,9 +1,14 @@
+use core::iter;
+use std::rc::Rc;
+
 use thiserror::Error;
 
 use crate::{
     chunk::{Chunk, OpCode},
-    scanner::{Scanner, Token, TokenType},
-    value::Value,
+    heap::Heap,
+    object::{Function, Object, UpvalueDescriptor},
+    scanner::{self, Scanner, Span, Token, TokenType},
+    value::{ObjRef, Value},
 };
 
 #[derive(PartialEq, PartialOrd, Eq, Ord)]
@@ -45,6 +50,15 @@ enum ParseFn {
     Binary,
     Literal,
     Number,
+    String,
+    Interpolation,
+    Variable,
+    And,
+    Or,
+    Call,
+    Dot,
+    This,
+    Super,
 }
 
 struct ParseRule {
@@ -54,28 +68,177 @@ struct ParseRule {
 }
 
 #[derive(Debug, Error)]
-pub enum ParserError<'src> {
-    #[error("[line {line}] Error at {location}: {msg}")]
+#[non_exhaustive]
+pub enum ParserError {
+    #[error("[line {}] Error at {location}: {msg}", span.line)]
     AtToken {
-        line: i32,
-        location: &'src str,
-        msg: &'src str,
+        span: Span,
+        location: String,
+        msg: String,
     },
-    #[error("[line {line}] Error: {msg}")]
-    General { line: i32, msg: &'src str },
+    #[error("[line {}] Error: {msg}", span.line)]
+    General { span: Span, msg: String },
+}
+
+impl ParserError {
+    #[inline]
+    #[must_use]
+    pub const fn code(&self) -> &'static str {
+        match *self {
+            Self::AtToken { .. } => "syntax-error",
+            Self::General { .. } => "scan-error",
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn message(&self) -> &str {
+        match *self {
+            Self::AtToken { ref msg, .. } | Self::General { ref msg, .. } => {
+                msg
+            }
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn span(&self) -> Span {
+        match *self {
+            Self::AtToken { span, .. } | Self::General { span, .. } => span,
+        }
+    }
 }
 
+const MAX_ARGS: usize = 255;
+
 enum ParseState {
     Prefix,
     Infix,
     Done,
 }
 
+struct Local<'src> {
+    name: &'src str,
+    depth: Option<usize>,
+    is_captured: bool,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum FunctionType {
+    Function,
+    Initializer,
+    Method,
+    Script,
+}
+
+struct ClassCompiler {
+    has_superclass: bool,
+}
+
+struct Compiler<'src> {
+    r#type: FunctionType,
+    name: Option<&'src str>,
+    arity: usize,
+    chunk: Chunk,
+    locals: Vec<Local<'src>>,
+    upvalues: Vec<UpvalueDescriptor>,
+    scope_depth: usize,
+}
+
+impl<'src> Compiler<'src> {
+    fn new(r#type: FunctionType, name: Option<&'src str>) -> Self {
+        Self {
+            r#type,
+            name,
+            arity: 0,
+            chunk: Chunk::new(),
+            locals: vec![Local {
+                name: if r#type == FunctionType::Function {
+                    ""
+                } else {
+                    "this"
+                },
+                depth: Some(0),
+                is_captured: false,
+            }],
+            upvalues: Vec::new(),
+            scope_depth: 0,
+        }
+    }
+
+    fn resolve_local(&self, name: &str) -> Result<Option<usize>, &'static str> {
+        let Some((slot, local)) = self
+            .locals
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|&(_, local)| local.name == name)
+        else {
+            return Ok(None);
+        };
+
+        if local.depth.is_none() {
+            return Err("Can't read local variable in its own initializer.");
+        }
+
+        Ok(Some(slot))
+    }
+
+    fn resolve_upvalue(
+        &mut self,
+        enclosing: &mut [Self],
+        name: &str,
+    ) -> Result<Option<usize>, &'static str> {
+        let Some((parent, rest)) = enclosing.split_last_mut() else {
+            return Ok(None);
+        };
+
+        if let Some(slot) = parent.resolve_local(name)? {
+            if let Some(local) = parent.locals.get_mut(slot) {
+                local.is_captured = true;
+            }
+            return Ok(Some(self.add_upvalue(slot, true)));
+        }
+
+        if let Some(index) = parent.resolve_upvalue(rest, name)? {
+            return Ok(Some(self.add_upvalue(index, false)));
+        }
+
+        Ok(None)
+    }
+
+    fn add_upvalue(&mut self, index: usize, is_local: bool) -> usize {
+        let upvalue = UpvalueDescriptor { index, is_local };
+
+        if let Some(existing) =
+            self.upvalues.iter().position(|&other| other == upvalue)
+        {
+            return existing;
+        }
+
+        self.upvalues.push(upvalue);
+        self.upvalues.len() - 1
+    }
+}
+
+struct Checkpoint {
+    compilers: usize,
+    locals: usize,
+    scope_depth: usize,
+    classes: usize,
+}
+
 struct Parser<'src, 'scanner> {
     current: Option<Token<'src>>,
     previous: Option<Token<'src>>,
     scanner: &'scanner mut Scanner<'src>,
-    chunk: &'src mut Chunk,
+    compiler: Compiler<'src>,
+    enclosing: Vec<Compiler<'src>>,
+    classes: Vec<ClassCompiler>,
+    errors: Vec<ParserError>,
+    panic_mode: bool,
+    heap: &'src mut Heap,
+    vm_roots: &'src [Value],
 }
 
 impl<'src, 'scanner> Parser<'src, 'scanner> {
@@ -87,7 +250,7 @@ impl<'src, 'scanner> Parser<'src, 'scanner> {
             length exactly matches the number of `TokenType` variants.
         "#
     )]
-    const LOOKUP_RULES: [ParseRule; 40] = {
+    const LOOKUP_RULES: [ParseRule; 42] = {
         const DEFAULT: ParseRule = ParseRule {
             prefix: None,
             infix: None,
@@ -98,8 +261,13 @@ impl<'src, 'scanner> Parser<'src, 'scanner> {
 
         rules[TokenType::LeftParen as usize] = ParseRule {
             prefix: Some(ParseFn::Grouping),
-            infix: None,
-            precedence: Precedence::None,
+            infix: Some(ParseFn::Call),
+            precedence: Precedence::Call,
+        };
+        rules[TokenType::Dot as usize] = ParseRule {
+            prefix: None,
+            infix: Some(ParseFn::Dot),
+            precedence: Precedence::Call,
         };
         rules[TokenType::Minus as usize] = ParseRule {
             prefix: Some(ParseFn::Unary),
@@ -156,6 +324,31 @@ impl<'src, 'scanner> Parser<'src, 'scanner> {
             infix: Some(ParseFn::Binary),
             precedence: Precedence::Comparison,
         };
+        rules[TokenType::Identifier as usize] = ParseRule {
+            prefix: Some(ParseFn::Variable),
+            infix: None,
+            precedence: Precedence::None,
+        };
+        rules[TokenType::And as usize] = ParseRule {
+            prefix: None,
+            infix: Some(ParseFn::And),
+            precedence: Precedence::And,
+        };
+        rules[TokenType::Or as usize] = ParseRule {
+            prefix: None,
+            infix: Some(ParseFn::Or),
+            precedence: Precedence::Or,
+        };
+        rules[TokenType::String as usize] = ParseRule {
+            prefix: Some(ParseFn::String),
+            infix: None,
+            precedence: Precedence::None,
+        };
+        rules[TokenType::Interpolation as usize] = ParseRule {
+            prefix: Some(ParseFn::Interpolation),
+            infix: None,
+            precedence: Precedence::None,
+        };
         rules[TokenType::Number as usize] = ParseRule {
             prefix: Some(ParseFn::Number),
             infix: None,
@@ -166,6 +359,16 @@ impl<'src, 'scanner> Parser<'src, 'scanner> {
             infix: None,
             precedence: Precedence::None,
         };
+        rules[TokenType::Super as usize] = ParseRule {
+            prefix: Some(ParseFn::Super),
+            infix: None,
+            precedence: Precedence::None,
+        };
+        rules[TokenType::This as usize] = ParseRule {
+            prefix: Some(ParseFn::This),
+            infix: None,
+            precedence: Precedence::None,
+        };
         rules[TokenType::True as usize] = ParseRule {
             prefix: Some(ParseFn::Literal),
             infix: None,
@@ -180,15 +383,51 @@ impl<'src, 'scanner> Parser<'src, 'scanner> {
         rules
     };
 
-    const fn new(
+    fn new(
         scanner: &'scanner mut Scanner<'src>,
-        chunk: &'src mut Chunk,
+        heap: &'src mut Heap,
+        vm_roots: &'src [Value],
     ) -> Self {
         Self {
             current: None,
             previous: None,
             scanner,
-            chunk,
+            compiler: Compiler::new(FunctionType::Script, None),
+            enclosing: Vec::new(),
+            classes: Vec::new(),
+            errors: Vec::new(),
+            panic_mode: false,
+            heap,
+            vm_roots,
+        }
+    }
+
+    fn alloc(&mut self, object: Object) -> ObjRef {
+        let obj_ref = self.heap.alloc(object);
+        self.collect_if_needed(obj_ref);
+        obj_ref
+    }
+
+    fn intern(&mut self, string: &str) -> ObjRef {
+        let obj_ref = self.heap.intern(string);
+        self.collect_if_needed(obj_ref);
+        obj_ref
+    }
+
+    fn collect_if_needed(&mut self, obj_ref: ObjRef) {
+        if self.heap.should_collect() {
+            let compiler_roots = self
+                .enclosing
+                .iter()
+                .chain(iter::once(&self.compiler))
+                .flat_map(|compiler| compiler.chunk.constants.iter().copied());
+            self.heap.collect_garbage(
+                self.vm_roots
+                    .iter()
+                    .copied()
+                    .chain(compiler_roots)
+                    .chain(iter::once(Value::Obj(obj_ref))),
+            );
         }
     }
 
@@ -204,74 +443,148 @@ impl<'src, 'scanner> Parser<'src, 'scanner> {
         &Self::LOOKUP_RULES[r#type as usize]
     }
 
-    fn advance(&mut self) -> Result<(), ParserError<'src>> {
+    fn advance(&mut self) {
         self.previous = self.current.take();
 
-        let current = self.scanner.scan_token();
+        loop {
+            let current = self.scanner.scan_token();
+            if current.r#type != TokenType::Error {
+                self.current = Some(current);
+                break;
+            }
 
-        if current.r#type == TokenType::Error {
-            Err(ParserError::AtToken {
-                line: current.line,
-                location: current.lexeme,
-                msg: current.lexeme,
-            })
-        } else {
-            self.current = Some(current);
-            Ok(())
+            self.report(ParserError::General {
+                span: current.span,
+                msg: current.lexeme.to_owned(),
+            });
         }
     }
 
+    fn report(&mut self, err: ParserError) {
+        if self.panic_mode {
+            return;
+        }
+        self.panic_mode = true;
+        self.errors.push(err);
+    }
+
+    fn synchronize(&mut self) {
+        self.panic_mode = false;
+
+        while !self.check(TokenType::Eof) {
+            if self
+                .previous
+                .as_ref()
+                .is_some_and(|previous| previous.r#type == TokenType::Semicolon)
+            {
+                return;
+            }
+
+            if self.current.as_ref().is_some_and(|current| {
+                matches!(
+                    current.r#type,
+                    TokenType::Class
+                        | TokenType::Fun
+                        | TokenType::Var
+                        | TokenType::For
+                        | TokenType::If
+                        | TokenType::While
+                        | TokenType::Print
+                        | TokenType::Return
+                )
+            }) {
+                return;
+            }
+
+            self.advance();
+        }
+    }
+
+    const fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            compilers: self.enclosing.len(),
+            locals: self.compiler.locals.len(),
+            scope_depth: self.compiler.scope_depth,
+            classes: self.classes.len(),
+        }
+    }
+
+    fn restore(&mut self, checkpoint: &Checkpoint) {
+        while self.enclosing.len() > checkpoint.compilers {
+            if let Some(enclosing) = self.enclosing.pop() {
+                self.compiler = enclosing;
+            }
+        }
+        self.compiler.locals.truncate(checkpoint.locals);
+        self.compiler.scope_depth = checkpoint.scope_depth;
+        self.classes.truncate(checkpoint.classes);
+    }
+
     fn consume(
         &mut self,
         r#type: TokenType,
-        msg: &'src str,
-    ) -> Result<(), ParserError<'src>> {
-        let current = self.current.as_ref().ok_or(ParserError::AtToken {
-            line: 0,
-            location: "end of input",
-            msg,
-        })?;
+        msg: &str,
+    ) -> Result<(), ParserError> {
+        let current =
+            self.current.as_ref().ok_or_else(|| ParserError::AtToken {
+                span: Span::default(),
+                location: "end of input".to_owned(),
+                msg: msg.to_owned(),
+            })?;
 
         if current.r#type == r#type {
-            self.advance()?;
+            self.advance();
             Ok(())
         } else {
             Err(ParserError::AtToken {
-                line: current.line,
-                location: current.lexeme,
-                msg,
+                span: current.span,
+                location: current.lexeme.to_owned(),
+                msg: msg.to_owned(),
             })
         }
     }
 
+    fn check(&self, r#type: TokenType) -> bool {
+        self.current
+            .as_ref()
+            .is_some_and(|current| current.r#type == r#type)
+    }
+
+    fn matches(&mut self, r#type: TokenType) -> bool {
+        if !self.check(r#type) {
+            return false;
+        }
+        self.advance();
+        true
+    }
+
     fn parse_precedence(
         &mut self,
         precedence: &Precedence,
-    ) -> Result<(), ParserError<'src>> {
-        self.advance()?;
+    ) -> Result<(), ParserError> {
+        self.advance();
+        let can_assign = *precedence <= Precedence::Assignment;
         let mut state = ParseState::Prefix;
         loop {
             match state {
                 ParseState::Prefix => {
-                    let previous =
-                        self.previous.as_ref().ok_or(ParserError::General {
-                            line: 0,
-                            msg: "No previous token in prefix state.",
-                        })?;
+                    let previous = self.previous.as_ref().ok_or_else(|| {
+                        ParserError::General {
+                            span: Span::default(),
+                            msg: "No previous token in prefix state."
+                                .to_owned(),
+                        }
+                    })?;
                     let rule = Self::get_rule(previous.r#type);
                     let prefix_rule =
-                        rule.prefix.as_ref().ok_or(ParserError::AtToken {
-                            line: previous.line,
-                            location: previous.lexeme,
-                            msg: "Expect expression.",
+                        rule.prefix.as_ref().ok_or_else(|| {
+                            ParserError::AtToken {
+                                span: previous.span,
+                                location: previous.lexeme.to_owned(),
+                                msg: "Expect expression.".to_owned(),
+                            }
                         })?;
-                    match *prefix_rule {
-                        ParseFn::Unary => self.unary()?,
-                        ParseFn::Binary => self.binary()?,
-                        ParseFn::Grouping => self.grouping()?,
-                        ParseFn::Literal => self.literal()?,
-                        ParseFn::Number => self.number()?,
-                    }
+                    self.apply_parse_fn(prefix_rule, can_assign)?;
                     state = ParseState::Infix;
                 }
                 ParseState::Infix => {
@@ -287,101 +600,702 @@ impl<'src, 'scanner> Parser<'src, 'scanner> {
                         continue;
                     }
 
-                    self.advance()?;
+                    self.advance();
 
                     let Some(infix_rule) = rule.infix.as_ref() else {
                         state = ParseState::Done;
                         continue;
                     };
 
-                    match *infix_rule {
-                        ParseFn::Unary => self.unary()?,
-                        ParseFn::Binary => self.binary()?,
-                        ParseFn::Grouping => self.grouping()?,
-                        ParseFn::Literal => self.literal()?,
-                        ParseFn::Number => self.number()?,
+                    self.apply_parse_fn(infix_rule, can_assign)?;
+                }
+                ParseState::Done => {
+                    if can_assign && self.matches(TokenType::Equal) {
+                        let previous =
+                            self.previous.as_ref().ok_or_else(|| {
+                                ParserError::General {
+                                    span: Span::default(),
+                                    msg: "No previous token in assignment."
+                                        .to_owned(),
+                                }
+                            })?;
+                        break Err(ParserError::AtToken {
+                            span: previous.span,
+                            location: previous.lexeme.to_owned(),
+                            msg: "Invalid assignment target.".to_owned(),
+                        });
                     }
+                    break Ok(());
                 }
-                ParseState::Done => break Ok(()),
             }
         }
     }
 
-    fn expression(&mut self) -> Result<(), ParserError<'src>> {
+    fn apply_parse_fn(
+        &mut self,
+        parse_fn: &ParseFn,
+        can_assign: bool,
+    ) -> Result<(), ParserError> {
+        match *parse_fn {
+            ParseFn::Unary => self.unary(),
+            ParseFn::Binary => self.binary(),
+            ParseFn::Grouping => self.grouping(),
+            ParseFn::Literal => self.literal(),
+            ParseFn::Number => self.number(),
+            ParseFn::String => self.string(),
+            ParseFn::Interpolation => self.interpolation(),
+            ParseFn::Variable => self.variable(can_assign),
+            ParseFn::And => self.and(),
+            ParseFn::Or => self.or(),
+            ParseFn::Call => self.call(),
+            ParseFn::Dot => self.dot(can_assign),
+            ParseFn::This => self.this(),
+            ParseFn::Super => self.super_(),
+        }
+    }
+
+    fn expression(&mut self) -> Result<(), ParserError> {
         self.parse_precedence(&Precedence::Assignment)
     }
 
-    fn unary(&mut self) -> Result<(), ParserError<'src>> {
-        let previous = self.previous.as_ref().ok_or(ParserError::General {
-            line: 0,
-            msg: "No previous token in binary.",
-        })?;
+    fn declaration(&mut self) {
+        let checkpoint = self.checkpoint();
+        if let Err(err) = self.try_declaration() {
+            self.report(err);
+            self.restore(&checkpoint);
+        }
+
+        if self.panic_mode {
+            self.synchronize();
+        }
+    }
+
+    fn try_declaration(&mut self) -> Result<(), ParserError> {
+        if self.matches(TokenType::Class) {
+            self.class_declaration()
+        } else if self.matches(TokenType::Fun) {
+            self.fun_declaration()
+        } else if self.matches(TokenType::Var) {
+            self.var_declaration()
+        } else {
+            self.statement()
+        }
+    }
+
+    fn class_declaration(&mut self) -> Result<(), ParserError> {
+        self.consume(TokenType::Identifier, "Expect class name.")?;
+        let previous =
+            self.previous.as_ref().ok_or_else(|| ParserError::General {
+                span: Span::default(),
+                msg: "No previous token in class declaration.".to_owned(),
+            })?;
+        let (name, span) = (previous.lexeme, previous.span);
+
+        let name_constant = self.identifier_constant(name);
+        self.declare_variable(name)?;
+
+        self.compiler
+            .chunk
+            .write_opcode(OpCode::Class(name_constant), span);
+        self.define_variable(name_constant);
+
+        self.classes.push(ClassCompiler {
+            has_superclass: false,
+        });
+
+        if self.matches(TokenType::Less) {
+            self.consume(TokenType::Identifier, "Expect superclass name.")?;
+            self.variable(false)?;
+            if self
+                .previous
+                .as_ref()
+                .is_some_and(|previous| previous.lexeme == name)
+            {
+                return Err(self.error("A class can't inherit from itself."));
+            }
+
+            self.begin_scope();
+            self.compiler.locals.push(Local {
+                name: "super",
+                depth: None,
+                is_captured: false,
+            });
+            self.define_variable(0);
+
+            self.named_variable(name, span, false)?;
+            self.compiler
+                .chunk
+                .write_opcode(OpCode::Inherit, self.previous_span());
+            if let Some(class) = self.classes.last_mut() {
+                class.has_superclass = true;
+            }
+        }
+
+        self.named_variable(name, span, false)?;
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+        while !self.check(TokenType::RightBrace) && !self.check(TokenType::Eof)
+        {
+            self.method()?;
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
+        self.compiler
+            .chunk
+            .write_opcode(OpCode::Pop, self.previous_span());
+
+        if self.classes.pop().is_some_and(|class| class.has_superclass) {
+            self.end_scope();
+        }
+
+        Ok(())
+    }
+
+    fn method(&mut self) -> Result<(), ParserError> {
+        self.consume(TokenType::Identifier, "Expect method name.")?;
+        let previous =
+            self.previous.as_ref().ok_or_else(|| ParserError::General {
+                span: Span::default(),
+                msg: "No previous token in method declaration.".to_owned(),
+            })?;
+        let (name, span) = (previous.lexeme, previous.span);
+        let name_constant = self.identifier_constant(name);
+
+        let r#type = if name == "init" {
+            FunctionType::Initializer
+        } else {
+            FunctionType::Method
+        };
+        self.function(r#type)?;
+
+        self.compiler
+            .chunk
+            .write_opcode(OpCode::Method(name_constant), span);
+        Ok(())
+    }
+
+    fn fun_declaration(&mut self) -> Result<(), ParserError> {
+        let global = self.parse_variable("Expect function name.")?;
+        self.mark_initialized();
+        self.function(FunctionType::Function)?;
+        self.define_variable(global);
+        Ok(())
+    }
+
+    fn function(&mut self, r#type: FunctionType) -> Result<(), ParserError> {
+        let name = self.previous.as_ref().map(|previous| previous.lexeme);
+        let enclosing =
+            core::mem::replace(&mut self.compiler, Compiler::new(r#type, name));
+        self.enclosing.push(enclosing);
+        self.begin_scope();
+
+        self.consume(TokenType::LeftParen, "Expect '(' after function name.")?;
+        if !self.check(TokenType::RightParen) {
+            loop {
+                self.compiler.arity += 1;
+                if self.compiler.arity > MAX_ARGS {
+                    return Err(self.error_at_current(
+                        "Can't have more than 255 parameters.",
+                    ));
+                }
+                let constant = self.parse_variable("Expect parameter name.")?;
+                self.define_variable(constant);
+
+                if !self.matches(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
+        self.consume(TokenType::LeftBrace, "Expect '{' before function body.")?;
+        self.block()?;
+
+        let function = self.end_compiler();
+        let function = self.alloc(Object::Function(Rc::new(function)));
+        let const_idx = self.compiler.chunk.add_constant(Value::Obj(function));
+        self.compiler
+            .chunk
+            .write_opcode(OpCode::Closure(const_idx), self.previous_span());
+        Ok(())
+    }
+
+    fn end_compiler(&mut self) -> Function {
+        self.emit_return();
+
+        let enclosing = self.enclosing.pop();
+        let compiler = match enclosing {
+            Some(enclosing) => {
+                core::mem::replace(&mut self.compiler, enclosing)
+            }
+            None => core::mem::replace(
+                &mut self.compiler,
+                Compiler::new(FunctionType::Script, None),
+            ),
+        };
+
+        Function {
+            arity: compiler.arity,
+            chunk: compiler.chunk,
+            name: compiler.name.map(str::to_owned),
+            upvalues: compiler.upvalues,
+        }
+    }
+
+    fn emit_return(&mut self) {
+        let code = if self.compiler.r#type == FunctionType::Initializer {
+            OpCode::GetLocal(0)
+        } else {
+            OpCode::Nil
+        };
+        self.compiler.chunk.write_opcode(code, self.previous_span());
+        self.compiler
+            .chunk
+            .write_opcode(OpCode::Return, self.previous_span());
+    }
+
+    fn var_declaration(&mut self) -> Result<(), ParserError> {
+        let global = self.parse_variable("Expect variable name.")?;
+
+        if self.matches(TokenType::Equal) {
+            self.expression()?;
+        } else {
+            self.compiler
+                .chunk
+                .write_opcode(OpCode::Nil, self.previous_span());
+        }
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.",
+        )?;
+
+        self.define_variable(global);
+        Ok(())
+    }
+
+    fn parse_variable(&mut self, msg: &str) -> Result<usize, ParserError> {
+        self.consume(TokenType::Identifier, msg)?;
+        let previous =
+            self.previous.as_ref().ok_or_else(|| ParserError::General {
+                span: Span::default(),
+                msg: "No previous token in variable declaration.".to_owned(),
+            })?;
+        let name = previous.lexeme;
+
+        self.declare_variable(name)?;
+        if self.compiler.scope_depth > 0 {
+            return Ok(0);
+        }
+
+        Ok(self.identifier_constant(name))
+    }
+
+    fn declare_variable(&mut self, name: &'src str) -> Result<(), ParserError> {
+        if self.compiler.scope_depth == 0 {
+            return Ok(());
+        }
+
+        let already_declared = self
+            .compiler
+            .locals
+            .iter()
+            .rev()
+            .take_while(|local| {
+                local
+                    .depth
+                    .is_none_or(|depth| depth >= self.compiler.scope_depth)
+            })
+            .any(|local| local.name == name);
+        if already_declared {
+            return Err(
+                self.error("Already a variable with this name in this scope.")
+            );
+        }
+
+        self.compiler.locals.push(Local {
+            name,
+            depth: None,
+            is_captured: false,
+        });
+        Ok(())
+    }
+
+    fn define_variable(&mut self, global: usize) {
+        if self.compiler.scope_depth > 0 {
+            self.mark_initialized();
+            return;
+        }
+
+        self.compiler
+            .chunk
+            .write_opcode(OpCode::DefineGlobal(global), self.previous_span());
+    }
+
+    fn mark_initialized(&mut self) {
+        if self.compiler.scope_depth == 0 {
+            return;
+        }
+        if let Some(local) = self.compiler.locals.last_mut() {
+            local.depth = Some(self.compiler.scope_depth);
+        }
+    }
+
+    fn resolve_local(&self, name: &str) -> Result<Option<usize>, ParserError> {
+        self.compiler
+            .resolve_local(name)
+            .map_err(|msg| self.error(msg))
+    }
+
+    fn resolve_upvalue(
+        &mut self,
+        name: &str,
+    ) -> Result<Option<usize>, ParserError> {
+        self.compiler
+            .resolve_upvalue(&mut self.enclosing, name)
+            .map_err(|msg| self.error(msg))
+    }
+
+    fn identifier_constant(&mut self, name: &str) -> usize {
+        let name = self.intern(name);
+        self.compiler.chunk.add_constant(Value::Obj(name))
+    }
+
+    fn statement(&mut self) -> Result<(), ParserError> {
+        if self.matches(TokenType::Print) {
+            self.print_statement()
+        } else if self.matches(TokenType::For) {
+            self.for_statement()
+        } else if self.matches(TokenType::If) {
+            self.if_statement()
+        } else if self.matches(TokenType::Return) {
+            self.return_statement()
+        } else if self.matches(TokenType::While) {
+            self.while_statement()
+        } else if self.matches(TokenType::LeftBrace) {
+            self.begin_scope();
+            self.block()?;
+            self.end_scope();
+            Ok(())
+        } else {
+            self.expression_statement()
+        }
+    }
+
+    fn if_statement(&mut self) -> Result<(), ParserError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
+        self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
+
+        let then_jump = self.emit_jump(OpCode::JumpIfFalse(0));
+        self.compiler
+            .chunk
+            .write_opcode(OpCode::Pop, self.previous_span());
+        self.statement()?;
+
+        let else_jump = self.emit_jump(OpCode::Jump(0));
+
+        self.patch_jump(then_jump)?;
+        self.compiler
+            .chunk
+            .write_opcode(OpCode::Pop, self.previous_span());
+
+        if self.matches(TokenType::Else) {
+            self.statement()?;
+        }
+        self.patch_jump(else_jump)
+    }
+
+    fn return_statement(&mut self) -> Result<(), ParserError> {
+        if self.compiler.r#type == FunctionType::Script {
+            return Err(self.error("Can't return from top-level code."));
+        }
+
+        if self.matches(TokenType::Semicolon) {
+            self.emit_return();
+        } else {
+            if self.compiler.r#type == FunctionType::Initializer {
+                return Err(
+                    self.error("Can't return a value from an initializer.")
+                );
+            }
+
+            self.expression()?;
+            self.consume(
+                TokenType::Semicolon,
+                "Expect ';' after return value.",
+            )?;
+            self.compiler
+                .chunk
+                .write_opcode(OpCode::Return, self.previous_span());
+        }
+
+        Ok(())
+    }
+
+    fn while_statement(&mut self) -> Result<(), ParserError> {
+        let loop_start = self.compiler.chunk.codes.len();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
+        self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
+
+        let exit_jump = self.emit_jump(OpCode::JumpIfFalse(0));
+        self.compiler
+            .chunk
+            .write_opcode(OpCode::Pop, self.previous_span());
+        self.statement()?;
+        self.emit_loop(loop_start);
+
+        self.patch_jump(exit_jump)?;
+        self.compiler
+            .chunk
+            .write_opcode(OpCode::Pop, self.previous_span());
+        Ok(())
+    }
+
+    fn for_statement(&mut self) -> Result<(), ParserError> {
+        self.begin_scope();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
+        if self.matches(TokenType::Semicolon) {
+            // No initializer.
+        } else if self.matches(TokenType::Var) {
+            self.var_declaration()?;
+        } else {
+            self.expression_statement()?;
+        }
+
+        let mut loop_start = self.compiler.chunk.codes.len();
+        let mut exit_jump = None;
+        if !self.matches(TokenType::Semicolon) {
+            self.expression()?;
+            self.consume(
+                TokenType::Semicolon,
+                "Expect ';' after loop condition.",
+            )?;
+
+            exit_jump = Some(self.emit_jump(OpCode::JumpIfFalse(0)));
+            self.compiler
+                .chunk
+                .write_opcode(OpCode::Pop, self.previous_span());
+        }
+
+        if !self.matches(TokenType::RightParen) {
+            let body_jump = self.emit_jump(OpCode::Jump(0));
+            let increment_start = self.compiler.chunk.codes.len();
+            self.expression()?;
+            self.compiler
+                .chunk
+                .write_opcode(OpCode::Pop, self.previous_span());
+            self.consume(
+                TokenType::RightParen,
+                "Expect ')' after for clauses.",
+            )?;
+
+            self.emit_loop(loop_start);
+            loop_start = increment_start;
+            self.patch_jump(body_jump)?;
+        }
+
+        self.statement()?;
+        self.emit_loop(loop_start);
+
+        if let Some(exit_jump) = exit_jump {
+            self.patch_jump(exit_jump)?;
+            self.compiler
+                .chunk
+                .write_opcode(OpCode::Pop, self.previous_span());
+        }
+
+        self.end_scope();
+        Ok(())
+    }
+
+    fn emit_jump(&mut self, code: OpCode) -> usize {
+        self.compiler.chunk.write_opcode(code, self.previous_span());
+        self.compiler.chunk.codes.len() - 1
+    }
+
+    fn patch_jump(&mut self, offset: usize) -> Result<(), ParserError> {
+        let jump = self.compiler.chunk.codes.len() - offset - 1;
+
+        if let Some(
+            &mut (OpCode::Jump(ref mut target)
+            | OpCode::JumpIfFalse(ref mut target)),
+        ) = self.compiler.chunk.codes.get_mut(offset)
+        {
+            *target = jump;
+            Ok(())
+        } else {
+            Err(ParserError::General {
+                span: self.previous_span(),
+                msg: "Patched instruction is not a jump.".to_owned(),
+            })
+        }
+    }
+
+    fn emit_loop(&mut self, loop_start: usize) {
+        let offset = self.compiler.chunk.codes.len() - loop_start + 1;
+        self.compiler
+            .chunk
+            .write_opcode(OpCode::Loop(offset), self.previous_span());
+    }
+
+    fn block(&mut self) -> Result<(), ParserError> {
+        while !self.check(TokenType::RightBrace) && !self.check(TokenType::Eof)
+        {
+            self.declaration();
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after block.")
+    }
+
+    const fn begin_scope(&mut self) {
+        self.compiler.scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        self.compiler.scope_depth -= 1;
+
+        while let Some(local) = self
+            .compiler
+            .locals
+            .pop_if(|local| local.depth > Some(self.compiler.scope_depth))
+        {
+            let code = if local.is_captured {
+                OpCode::CloseUpvalue
+            } else {
+                OpCode::Pop
+            };
+            self.compiler.chunk.write_opcode(code, self.previous_span());
+        }
+    }
+
+    fn print_statement(&mut self) -> Result<(), ParserError> {
+        let span = self.previous_span();
+        self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
+        self.compiler.chunk.write_opcode(OpCode::Print, span);
+        Ok(())
+    }
+
+    fn expression_statement(&mut self) -> Result<(), ParserError> {
+        self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
+        self.compiler
+            .chunk
+            .write_opcode(OpCode::Pop, self.previous_span());
+        Ok(())
+    }
+
+    fn error(&self, msg: &str) -> ParserError {
+        self.previous.as_ref().map_or_else(
+            || ParserError::General {
+                span: Span::default(),
+                msg: msg.to_owned(),
+            },
+            |previous| ParserError::AtToken {
+                span: previous.span,
+                location: previous.lexeme.to_owned(),
+                msg: msg.to_owned(),
+            },
+        )
+    }
+
+    fn error_at_current(&self, msg: &str) -> ParserError {
+        self.current.as_ref().map_or_else(
+            || ParserError::General {
+                span: Span::default(),
+                msg: msg.to_owned(),
+            },
+            |current| ParserError::AtToken {
+                span: current.span,
+                location: current.lexeme.to_owned(),
+                msg: msg.to_owned(),
+            },
+        )
+    }
+
+    fn previous_span(&self) -> Span {
+        self.previous
+            .as_ref()
+            .map_or_else(Span::default, |previous| previous.span)
+    }
+
+    fn unary(&mut self) -> Result<(), ParserError> {
+        let previous =
+            self.previous.as_ref().ok_or_else(|| ParserError::General {
+                span: Span::default(),
+                msg: "No previous token in binary.".to_owned(),
+            })?;
         let op_type = previous.r#type;
-        let line = previous.line;
+        let span = previous.span;
 
         self.parse_precedence(&Precedence::Unary)?;
 
         match op_type {
-            TokenType::Bang => self.chunk.write_opcode(OpCode::Not, line),
-            TokenType::Minus => self.chunk.write_opcode(OpCode::Negate, line),
+            TokenType::Bang => {
+                self.compiler.chunk.write_opcode(OpCode::Not, span);
+            }
+            TokenType::Minus => {
+                self.compiler.chunk.write_opcode(OpCode::Negate, span);
+            }
             _ => {}
         }
 
         Ok(())
     }
 
-    fn binary(&mut self) -> Result<(), ParserError<'src>> {
-        let previous = self.previous.as_ref().ok_or(ParserError::General {
-            line: 0,
-            msg: "No previous token in binary.",
-        })?;
+    fn binary(&mut self) -> Result<(), ParserError> {
+        let previous =
+            self.previous.as_ref().ok_or_else(|| ParserError::General {
+                span: Span::default(),
+                msg: "No previous token in binary.".to_owned(),
+            })?;
         let op_type = previous.r#type;
-        let line = previous.line;
+        let span = previous.span;
         let rule = Self::get_rule(op_type);
         let next_precedence =
-            rule.precedence.next_level().ok_or(ParserError::General {
-                line,
-                msg: "Missing next precedence level.",
+            rule.precedence.next_level().ok_or_else(|| {
+                ParserError::General {
+                    span,
+                    msg: "Missing next precedence level.".to_owned(),
+                }
             })?;
 
         self.parse_precedence(&next_precedence)?;
 
         match op_type {
             TokenType::Bang => {
-                self.chunk.write_opcode(OpCode::Not, line);
+                self.compiler.chunk.write_opcode(OpCode::Not, span);
             }
             TokenType::BangEqual => {
-                self.chunk.write_opcode(OpCode::Equal, line);
-                self.chunk.write_opcode(OpCode::Not, line);
+                self.compiler.chunk.write_opcode(OpCode::Equal, span);
+                self.compiler.chunk.write_opcode(OpCode::Not, span);
             }
             TokenType::EqualEqual => {
-                self.chunk.write_opcode(OpCode::Equal, line);
+                self.compiler.chunk.write_opcode(OpCode::Equal, span);
             }
             TokenType::Greater => {
-                self.chunk.write_opcode(OpCode::Greater, line);
+                self.compiler.chunk.write_opcode(OpCode::Greater, span);
             }
             TokenType::GreaterEqual => {
-                self.chunk.write_opcode(OpCode::Less, line);
-                self.chunk.write_opcode(OpCode::Not, line);
+                self.compiler.chunk.write_opcode(OpCode::Less, span);
+                self.compiler.chunk.write_opcode(OpCode::Not, span);
             }
             TokenType::Less => {
-                self.chunk.write_opcode(OpCode::Less, line);
+                self.compiler.chunk.write_opcode(OpCode::Less, span);
             }
             TokenType::LessEqual => {
-                self.chunk.write_opcode(OpCode::Greater, line);
-                self.chunk.write_opcode(OpCode::Not, line);
+                self.compiler.chunk.write_opcode(OpCode::Greater, span);
+                self.compiler.chunk.write_opcode(OpCode::Not, span);
             }
             TokenType::Plus => {
-                self.chunk.write_opcode(OpCode::Add, line);
+                self.compiler.chunk.write_opcode(OpCode::Add, span);
             }
             TokenType::Minus => {
-                self.chunk.write_opcode(OpCode::Subtract, line);
+                self.compiler.chunk.write_opcode(OpCode::Subtract, span);
             }
             TokenType::Star => {
-                self.chunk.write_opcode(OpCode::Multiply, line);
+                self.compiler.chunk.write_opcode(OpCode::Multiply, span);
             }
             TokenType::Slash => {
-                self.chunk.write_opcode(OpCode::Divide, line);
+                self.compiler.chunk.write_opcode(OpCode::Divide, span);
             }
             _ => {}
         }
@@ -389,26 +1303,31 @@ impl<'src, 'scanner> Parser<'src, 'scanner> {
         Ok(())
     }
 
-    fn grouping(&mut self) -> Result<(), ParserError<'src>> {
+    fn grouping(&mut self) -> Result<(), ParserError> {
         self.expression()?;
         self.consume(TokenType::RightParen, "Expect ')' after expression.")
     }
 
-    fn literal(&mut self) -> Result<(), ParserError<'src>> {
-        let previous = self.previous.as_ref().ok_or(ParserError::General {
-            line: 0,
-            msg: "No previous token in binary.",
-        })?;
+    fn literal(&mut self) -> Result<(), ParserError> {
+        let previous =
+            self.previous.as_ref().ok_or_else(|| ParserError::General {
+                span: Span::default(),
+                msg: "No previous token in binary.".to_owned(),
+            })?;
 
         match previous.r#type {
             TokenType::False => {
-                self.chunk.write_opcode(OpCode::False, previous.line);
+                self.compiler
+                    .chunk
+                    .write_opcode(OpCode::False, previous.span);
             }
             TokenType::Nil => {
-                self.chunk.write_opcode(OpCode::Nil, previous.line);
+                self.compiler.chunk.write_opcode(OpCode::Nil, previous.span);
             }
             TokenType::True => {
-                self.chunk.write_opcode(OpCode::True, previous.line);
+                self.compiler
+                    .chunk
+                    .write_opcode(OpCode::True, previous.span);
             }
             _ => {}
         }
@@ -416,48 +1335,363 @@ impl<'src, 'scanner> Parser<'src, 'scanner> {
         Ok(())
     }
 
-    fn number(&mut self) -> Result<(), ParserError<'src>> {
-        let previous = self.previous.as_ref().ok_or(ParserError::General {
-            line: 0,
-            msg: "No previous token in binary.",
+    fn number(&mut self) -> Result<(), ParserError> {
+        let previous =
+            self.previous.as_ref().ok_or_else(|| ParserError::General {
+                span: Span::default(),
+                msg: "No previous token in binary.".to_owned(),
+            })?;
+
+        let number = parse_number(previous.lexeme).ok_or_else(|| {
+            ParserError::AtToken {
+                span: previous.span,
+                location: previous.lexeme.to_owned(),
+                msg: "Invalid number.".to_owned(),
+            }
         })?;
+        self.compiler
+            .chunk
+            .write_constant(Value::Number(number), previous.span);
+
+        Ok(())
+    }
+
+    fn variable(&mut self, can_assign: bool) -> Result<(), ParserError> {
+        let previous =
+            self.previous.as_ref().ok_or_else(|| ParserError::General {
+                span: Span::default(),
+                msg: "No previous token in variable.".to_owned(),
+            })?;
+        let (name, span) = (previous.lexeme, previous.span);
+        self.named_variable(name, span, can_assign)
+    }
+
+    fn named_variable(
+        &mut self,
+        name: &'src str,
+        span: Span,
+        can_assign: bool,
+    ) -> Result<(), ParserError> {
+        let (get_op, set_op) = if let Some(slot) = self.resolve_local(name)? {
+            (OpCode::GetLocal(slot), OpCode::SetLocal(slot))
+        } else if let Some(index) = self.resolve_upvalue(name)? {
+            (OpCode::GetUpvalue(index), OpCode::SetUpvalue(index))
+        } else {
+            let arg = self.identifier_constant(name);
+            (OpCode::GetGlobal(arg), OpCode::SetGlobal(arg))
+        };
+
+        if can_assign && self.matches(TokenType::Equal) {
+            self.expression()?;
+            self.compiler.chunk.write_opcode(set_op, span);
+        } else {
+            self.compiler.chunk.write_opcode(get_op, span);
+        }
+
+        Ok(())
+    }
+
+    fn call(&mut self) -> Result<(), ParserError> {
+        let span = self.previous_span();
+        let arg_count = self.argument_list()?;
+        self.compiler
+            .chunk
+            .write_opcode(OpCode::Call(arg_count), span);
+        Ok(())
+    }
+
+    fn dot(&mut self, can_assign: bool) -> Result<(), ParserError> {
+        self.consume(TokenType::Identifier, "Expect property name after '.'.")?;
+        let previous =
+            self.previous.as_ref().ok_or_else(|| ParserError::General {
+                span: Span::default(),
+                msg: "No previous token in property access.".to_owned(),
+            })?;
+        let (name, span) = (previous.lexeme, previous.span);
+        let name = self.identifier_constant(name);
+
+        if can_assign && self.matches(TokenType::Equal) {
+            self.expression()?;
+            self.compiler
+                .chunk
+                .write_opcode(OpCode::SetProperty(name), span);
+        } else if self.matches(TokenType::LeftParen) {
+            let arg_count = self.argument_list()?;
+            self.compiler
+                .chunk
+                .write_opcode(OpCode::Invoke(name, arg_count), span);
+        } else {
+            self.compiler
+                .chunk
+                .write_opcode(OpCode::GetProperty(name), span);
+        }
+
+        Ok(())
+    }
+
+    fn this(&mut self) -> Result<(), ParserError> {
+        if self.classes.is_empty() {
+            return Err(self.error("Can't use 'this' outside of a class."));
+        }
+
+        let span = self.previous_span();
+        self.named_variable("this", span, false)
+    }
+
+    fn super_(&mut self) -> Result<(), ParserError> {
+        match self.classes.last() {
+            None => {
+                return Err(self.error("Can't use 'super' outside of a class."));
+            }
+            Some(class) if !class.has_superclass => {
+                return Err(self.error(
+                    "Can't use 'super' in a class with no superclass.",
+                ));
+            }
+            Some(_) => {}
+        }
+
+        let span = self.previous_span();
+        self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+        self.consume(TokenType::Identifier, "Expect superclass method name.")?;
+        let name = self
+            .previous
+            .as_ref()
+            .map_or("", |previous| previous.lexeme);
+        let name = self.identifier_constant(name);
+
+        self.named_variable("this", span, false)?;
+        if self.matches(TokenType::LeftParen) {
+            let arg_count = self.argument_list()?;
+            self.named_variable("super", span, false)?;
+            self.compiler
+                .chunk
+                .write_opcode(OpCode::SuperInvoke(name, arg_count), span);
+        } else {
+            self.named_variable("super", span, false)?;
+            self.compiler
+                .chunk
+                .write_opcode(OpCode::GetSuper(name), span);
+        }
+
+        Ok(())
+    }
+
+    fn argument_list(&mut self) -> Result<usize, ParserError> {
+        let mut arg_count = 0;
+        if !self.check(TokenType::RightParen) {
+            loop {
+                self.expression()?;
+                if arg_count == MAX_ARGS {
+                    return Err(
+                        self.error("Can't have more than 255 arguments.")
+                    );
+                }
+                arg_count += 1;
+
+                if !self.matches(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+
+        self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
+        Ok(arg_count)
+    }
+
+    fn and(&mut self) -> Result<(), ParserError> {
+        let end_jump = self.emit_jump(OpCode::JumpIfFalse(0));
+
+        self.compiler
+            .chunk
+            .write_opcode(OpCode::Pop, self.previous_span());
+        self.parse_precedence(&Precedence::And)?;
+
+        self.patch_jump(end_jump)
+    }
+
+    fn or(&mut self) -> Result<(), ParserError> {
+        let else_jump = self.emit_jump(OpCode::JumpIfFalse(0));
+        let end_jump = self.emit_jump(OpCode::Jump(0));
+
+        self.patch_jump(else_jump)?;
+        self.compiler
+            .chunk
+            .write_opcode(OpCode::Pop, self.previous_span());
+
+        self.parse_precedence(&Precedence::Or)?;
+        self.patch_jump(end_jump)
+    }
+
+    fn string(&mut self) -> Result<(), ParserError> {
+        if let Some(previous) = self
+            .previous
+            .as_ref()
+            .filter(|previous| previous.lexeme.starts_with('}'))
+        {
+            return Err(ParserError::AtToken {
+                span: Span {
+                    end: previous.span.start + 1,
+                    ..previous.span
+                },
+                location: "}".to_owned(),
+                msg: "Expect expression.".to_owned(),
+            });
+        }
 
-        #[expect(
-            clippy::map_err_ignore,
-            reason = r#"
-                Specific parse errors contain details not usefor for end users.
-            "#
-        )]
-        let number: f64 =
-            previous.lexeme.parse().map_err(|_| ParserError::AtToken {
-                line: previous.line,
-                location: previous.lexeme,
-                msg: "Invalid number.",
+        self.string_segment()
+    }
+
+    fn string_segment(&mut self) -> Result<(), ParserError> {
+        let previous =
+            self.previous.as_ref().ok_or_else(|| ParserError::General {
+                span: Span::default(),
+                msg: "No previous token in string.".to_owned(),
             })?;
-        self.chunk
-            .write_constant(Value::Number(number), previous.line);
+
+        let contents = previous
+            .lexeme
+            .strip_prefix(['"', '}'])
+            .and_then(|lexeme| {
+                lexeme
+                    .strip_suffix('"')
+                    .or_else(|| lexeme.strip_suffix("${"))
+            })
+            .ok_or_else(|| ParserError::AtToken {
+                span: previous.span,
+                location: previous.lexeme.to_owned(),
+                msg: "Invalid string.".to_owned(),
+            })?;
+        let contents = scanner::unescape(contents);
+        let span = previous.span;
+
+        let string = self.intern(&contents);
+        self.compiler.chunk.write_constant(Value::Obj(string), span);
+
+        Ok(())
+    }
+
+    fn interpolation(&mut self) -> Result<(), ParserError> {
+        self.string_segment()?;
+
+        loop {
+            self.expression()?;
+            self.compiler
+                .chunk
+                .write_opcode(OpCode::Stringify, self.previous_span());
+            self.compiler
+                .chunk
+                .write_opcode(OpCode::Add, self.previous_span());
+
+            if !self.matches(TokenType::Interpolation) {
+                break;
+            }
+            self.string_segment()?;
+            self.compiler
+                .chunk
+                .write_opcode(OpCode::Add, self.previous_span());
+        }
+
+        self.consume(TokenType::String, "Expect end of string interpolation.")?;
+        self.string_segment()?;
+        self.compiler
+            .chunk
+            .write_opcode(OpCode::Add, self.previous_span());
 
         Ok(())
     }
 }
 
 #[inline]
-pub fn compile<'src>(
+pub(crate) fn compile<'src>(
     source: &'src str,
-    chunk: &'src mut Chunk,
-) -> Result<(), ParserError<'src>> {
+    heap: &'src mut Heap,
+    vm_roots: &'src [Value],
+) -> Result<Rc<Function>, Vec<ParserError>> {
     let mut scanner = Scanner::new(source);
-    let mut parser = Parser::new(&mut scanner, chunk);
+    let mut parser = Parser::new(&mut scanner, heap, vm_roots);
 
-    parser.advance()?;
-    parser.expression()?;
-    parser.consume(TokenType::Eof, "Expect end of expression.")?;
+    parser.advance();
+    while !parser.matches(TokenType::Eof) {
+        parser.declaration();
+    }
+
+    let function = parser.end_compiler();
+    if parser.errors.is_empty() {
+        Ok(Rc::new(function))
+    } else {
+        Err(parser.errors)
+    }
+}
 
-    let previous = parser.previous.ok_or(ParserError::General {
-        line: 0,
-        msg: "Failed to compile code.",
-    })?;
+fn parse_number(lexeme: &str) -> Option<f64> {
+    let digits: String = lexeme.chars().filter(|&char| char != '_').collect();
 
-    parser.chunk.write_opcode(OpCode::Return, previous.line);
-    Ok(())
+    if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        return parse_radix(hex, 16);
+    }
+    if let Some(binary) = digits
+        .strip_prefix("0b")
+        .or_else(|| digits.strip_prefix("0B"))
+    {
+        return parse_radix(binary, 2);
+    }
+
+    digits.parse().ok()
+}
+
+fn parse_radix(digits: &str, radix: u32) -> Option<f64> {
+    digits.chars().try_fold(0.0_f64, |number, char| {
+        let digit = f64::from(char.to_digit(radix)?);
+        Some(number.mul_add(f64::from(radix), digit))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compile, parse_number};
+    use crate::heap::Heap;
+
+    fn errors(source: &str) -> Vec<String> {
+        let mut heap = Heap::new();
+        compile(source, &mut heap, &[])
+            .err()
+            .map_or_else(Vec::new, |errors| {
+                errors
+                    .iter()
+                    .map(|error| error.message().to_owned())
+                    .collect()
+            })
+    }
+
+    #[test]
+    fn interpolation_compiles() {
+        assert!(errors(r#"var b = 1; print "a${b}c${"x${b}"}";"#).is_empty());
+    }
+
+    #[test]
+    fn empty_interpolation() {
+        assert_eq!(errors(r#"print "${}";"#), ["Expect expression."]);
+    }
+
+    #[test]
+    fn unterminated_interpolation() {
+        assert_eq!(
+            errors(r#"print "${1"#),
+            ["Expect end of string interpolation."]
+        );
+    }
+
+    #[test]
+    fn parse_number_strips_separators_and_prefixes() {
+        assert_eq!(parse_number("1_000"), Some(1000.0));
+        assert_eq!(parse_number("1_000.5e1"), Some(10005.0));
+        assert_eq!(parse_number("0x1F"), Some(31.0));
+        assert_eq!(parse_number("0XFF_FF"), Some(65535.0));
+        assert_eq!(parse_number("0b1010"), Some(10.0));
+        assert_eq!(parse_number("0B1_0"), Some(2.0));
+    }
 }
@@ -0,0 +1,161 @@
+use core::fmt::Write as _;
+
+use crate::{
+    scanner::Span,
+    vm::{InterpretError, TraceFrame},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[expect(
+    clippy::exhaustive_enums,
+    reason = r#"
+        Callers match on every format so that adding one forces them to
+        decide how it is reported.
+    "#
+)]
+pub enum ErrorFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+#[inline]
+pub fn report(err: &InterpretError, source: Option<&str>) {
+    match *err {
+        InterpretError::Compile(ref errors) => {
+            for error in errors {
+                eprintln!("{error}");
+                if let Some(source) = source {
+                    eprint!("{}", snippet(source, error.span()));
+                }
+            }
+        }
+        InterpretError::Runtime {
+            ref error,
+            ref trace,
+        } => {
+            eprintln!("{error}");
+            if let (Some(source), Some(frame)) = (source, trace.first()) {
+                eprint!("{}", snippet(source, frame.span));
+            }
+            for frame in trace {
+                eprintln!("{frame}");
+            }
+        }
+    }
+}
+
+#[inline]
+pub fn report_json(err: &InterpretError, file: &str) {
+    match *err {
+        InterpretError::Compile(ref errors) => {
+            for error in errors {
+                eprintln!(
+                    "{}",
+                    json_line(
+                        error.code(),
+                        error.message(),
+                        file,
+                        error.span(),
+                        &[]
+                    )
+                );
+            }
+        }
+        InterpretError::Runtime {
+            ref error,
+            ref trace,
+        } => {
+            let span =
+                trace.first().map_or_else(Span::default, |frame| frame.span);
+            eprintln!(
+                "{}",
+                json_line(error.code(), &error.to_string(), file, span, trace)
+            );
+        }
+    }
+}
+
+fn json_line(
+    code: &str,
+    message: &str,
+    file: &str,
+    span: Span,
+    trace: &[TraceFrame],
+) -> String {
+    let trace = trace
+        .iter()
+        .map(|frame| {
+            let function = frame.function.as_ref().map_or_else(
+                || "null".to_owned(),
+                |name| format!(r#""{}""#, escape_json(name)),
+            );
+            format!(
+                r#"{{"function":{function},"line":{},"column":{}}}"#,
+                frame.span.line, frame.span.column,
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+
+    format!(
+        r#"{{"severity":"error","code":"{}","message":"{}","file":"{}","line":{},"column":{},"span":{{"start":{},"end":{}}},"trace":[{trace}]}}"#,
+        escape_json(code),
+        escape_json(message),
+        escape_json(file),
+        span.line,
+        span.column,
+        span.start,
+        span.end,
+    )
+}
+
+fn escape_json(string: &str) -> String {
+    let mut out = String::with_capacity(string.len());
+    for char in string.chars() {
+        match char {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            char if char.is_control() => {
+                _ = write!(out, "\\u{:04x}", u32::from(char));
+            }
+            char => out.push(char),
+        }
+    }
+    out
+}
+
+fn snippet(source: &str, span: Span) -> String {
+    let Some(before) = source.get(..span.start) else {
+        return String::new();
+    };
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    let line_end = source
+        .get(span.start..)
+        .and_then(|rest| rest.find('\n'))
+        .map_or(source.len(), |idx| span.start + idx);
+    let Some(text) = source.get(line_start..line_end) else {
+        return String::new();
+    };
+
+    let width = source
+        .get(span.start..span.end.min(line_end))
+        .map_or(0, |lexeme| lexeme.chars().count());
+    let gutter = span.line.to_string();
+
+    let mut out = String::new();
+    _ = writeln!(out, "{gutter} | {}", text.replace('\t', " "));
+    _ = writeln!(
+        out,
+        "{:gutter_width$} | {:column$}^{}",
+        "",
+        "",
+        "~".repeat(width.saturating_sub(1)),
+        gutter_width = gutter.len(),
+        column = span.column.saturating_sub(1),
+    );
+    out
+}
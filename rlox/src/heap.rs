@@ -0,0 +1,357 @@
+use core::{
+    fmt::{self, Display, Formatter},
+    mem,
+};
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    chunk::{Chunk, OpCode},
+    object::{
+        BoundMethod, Class, Closure, Function, Instance, Object, Upvalue,
+    },
+    scanner::Span,
+    value::{ObjRef, Value},
+};
+
+const GC_HEAP_GROW_FACTOR: usize = 2;
+const GC_INITIAL_THRESHOLD: usize = 1024 * 1024;
+
+#[derive(Debug)]
+pub struct Heap {
+    objects: Vec<Option<Object>>,
+    marks: Vec<bool>,
+    sizes: Vec<usize>,
+    free_slots: Vec<usize>,
+    strings: HashMap<Rc<str>, ObjRef>,
+    gray: Vec<ObjRef>,
+    bytes_allocated: usize,
+    next_gc: usize,
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        Self {
+            objects: Vec::new(),
+            marks: Vec::new(),
+            sizes: Vec::new(),
+            free_slots: Vec::new(),
+            strings: HashMap::new(),
+            gray: Vec::new(),
+            bytes_allocated: 0,
+            next_gc: if cfg!(feature = "stress-gc") {
+                0
+            } else {
+                GC_INITIAL_THRESHOLD
+            },
+        }
+    }
+
+    pub fn alloc(&mut self, object: Object) -> ObjRef {
+        let size = size_of_object(&object);
+        self.bytes_allocated += size;
+
+        if let Some(idx) = self.free_slots.pop() {
+            if let (Some(slot), Some(slot_size)) =
+                (self.objects.get_mut(idx), self.sizes.get_mut(idx))
+            {
+                *slot = Some(object);
+                *slot_size = size;
+                return ObjRef::new(idx);
+            }
+        }
+
+        self.objects.push(Some(object));
+        self.marks.push(false);
+        self.sizes.push(size);
+        ObjRef::new(self.objects.len() - 1)
+    }
+
+    pub fn intern(&mut self, string: &str) -> ObjRef {
+        if let Some(&obj_ref) = self.strings.get(string) {
+            return obj_ref;
+        }
+
+        let string: Rc<str> = Rc::from(string);
+        let obj_ref = self.alloc(Object::String(Rc::clone(&string)));
+        self.strings.insert(string, obj_ref);
+        obj_ref
+    }
+
+    pub fn get(&self, obj_ref: ObjRef) -> Option<&Object> {
+        self.objects.get(obj_ref.idx())?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, obj_ref: ObjRef) -> Option<&mut Object> {
+        self.objects.get_mut(obj_ref.idx())?.as_mut()
+    }
+
+    pub const fn should_collect(&self) -> bool {
+        self.bytes_allocated > self.next_gc
+    }
+
+    pub fn collect_garbage<I>(&mut self, roots: I)
+    where
+        I: IntoIterator<Item = Value>,
+    {
+        for root in roots {
+            self.mark_value(root);
+        }
+        self.trace_references();
+        self.remove_white_strings();
+        self.sweep();
+
+        self.next_gc = if cfg!(feature = "stress-gc") {
+            self.bytes_allocated
+        } else {
+            self.bytes_allocated.max(GC_INITIAL_THRESHOLD) * GC_HEAP_GROW_FACTOR
+        };
+    }
+
+    fn mark_value(&mut self, value: Value) {
+        if let Value::Obj(obj_ref) = value {
+            self.mark_object(obj_ref);
+        }
+    }
+
+    fn mark_object(&mut self, obj_ref: ObjRef) {
+        if let Some(marked) = self.marks.get_mut(obj_ref.idx()) {
+            if !*marked {
+                *marked = true;
+                self.gray.push(obj_ref);
+            }
+        }
+    }
+
+    fn mark_chunk(&mut self, chunk: &Chunk) {
+        for &constant in &chunk.constants {
+            self.mark_value(constant);
+        }
+    }
+
+    fn trace_references(&mut self) {
+        while let Some(obj_ref) = self.gray.pop() {
+            self.blacken_object(obj_ref);
+        }
+    }
+
+    fn blacken_object(&mut self, obj_ref: ObjRef) {
+        let Some(object) = self.get(obj_ref) else {
+            return;
+        };
+
+        match *object {
+            Object::String(_) | Object::Native(_) => {}
+            Object::Function(ref function) => {
+                let function = Rc::clone(function);
+                self.mark_chunk(&function.chunk);
+            }
+            Object::Closure(ref closure) => {
+                let function = Rc::clone(&closure.function);
+                let upvalues = closure.upvalues.clone();
+                self.mark_chunk(&function.chunk);
+                for upvalue in upvalues {
+                    self.mark_object(upvalue);
+                }
+            }
+            Object::Upvalue(upvalue) => {
+                if let Upvalue::Closed(value) = upvalue {
+                    self.mark_value(value);
+                }
+            }
+            Object::Class(ref class) => {
+                let methods: Vec<(ObjRef, ObjRef)> = class
+                    .methods
+                    .iter()
+                    .map(|(&name, &method)| (name, method))
+                    .collect();
+                for (name, method) in methods {
+                    self.mark_object(name);
+                    self.mark_object(method);
+                }
+            }
+            Object::Instance(ref instance) => {
+                let class = instance.class;
+                let fields: Vec<(ObjRef, Value)> = instance
+                    .fields
+                    .iter()
+                    .map(|(&name, &value)| (name, value))
+                    .collect();
+                self.mark_object(class);
+                for (name, value) in fields {
+                    self.mark_object(name);
+                    self.mark_value(value);
+                }
+            }
+            Object::BoundMethod(bound) => {
+                self.mark_value(bound.receiver);
+                self.mark_object(bound.method);
+            }
+        }
+    }
+
+    fn remove_white_strings(&mut self) {
+        let marks = &self.marks;
+        self.strings.retain(|_, obj_ref| {
+            marks.get(obj_ref.idx()).is_some_and(|&marked| marked)
+        });
+    }
+
+    fn sweep(&mut self) {
+        for (idx, ((slot, marked), &size)) in self
+            .objects
+            .iter_mut()
+            .zip(&mut self.marks)
+            .zip(&self.sizes)
+            .enumerate()
+        {
+            if mem::take(marked) {
+                continue;
+            }
+            if slot.take().is_some() {
+                // Objects such as instances grow after allocation, so release
+                // exactly what `alloc` charged rather than their current size.
+                self.bytes_allocated -= size;
+                self.free_slots.push(idx);
+            }
+        }
+    }
+
+    pub fn as_string(&self, value: Value) -> Option<&str> {
+        if let Object::String(ref string) = *self.get(value.as_obj()?)? {
+            Some(string)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_function(&self, value: Value) -> Option<&Rc<Function>> {
+        if let Object::Function(ref function) = *self.get(value.as_obj()?)? {
+            Some(function)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_closure(&self, obj_ref: ObjRef) -> Option<&Closure> {
+        if let Object::Closure(ref closure) = *self.get(obj_ref)? {
+            Some(closure)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_class(&self, value: Value) -> Option<&Class> {
+        if let Object::Class(ref class) = *self.get(value.as_obj()?)? {
+            Some(class)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_class_mut(&mut self, value: Value) -> Option<&mut Class> {
+        if let Object::Class(ref mut class) = *self.get_mut(value.as_obj()?)? {
+            Some(class)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_bound_method(&self, value: Value) -> Option<BoundMethod> {
+        if let Object::BoundMethod(bound) = *self.get(value.as_obj()?)? {
+            Some(bound)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_upvalue_mut(&mut self, obj_ref: ObjRef) -> Option<&mut Upvalue> {
+        if let Object::Upvalue(ref mut upvalue) = *self.get_mut(obj_ref)? {
+            Some(upvalue)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_instance(&self, value: Value) -> Option<&Instance> {
+        if let Object::Instance(ref instance) = *self.get(value.as_obj()?)? {
+            Some(instance)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_instance_mut(&mut self, value: Value) -> Option<&mut Instance> {
+        if let Object::Instance(ref mut instance) =
+            *self.get_mut(value.as_obj()?)?
+        {
+            Some(instance)
+        } else {
+            None
+        }
+    }
+
+    pub const fn display(&self, value: Value) -> ValueDisplay<'_> {
+        ValueDisplay { heap: self, value }
+    }
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const fn size_of_chunk(chunk: &Chunk) -> usize {
+    chunk.codes.capacity() * size_of::<OpCode>()
+        + chunk.constants.capacity() * size_of::<Value>()
+        + chunk.spans.capacity() * size_of::<Span>()
+}
+
+fn size_of_object(object: &Object) -> usize {
+    size_of::<Object>()
+        + match *object {
+            Object::String(ref string) => string.len(),
+            Object::Function(ref function) => size_of_chunk(&function.chunk),
+            Object::Closure(ref closure) => {
+                closure.upvalues.capacity() * size_of::<ObjRef>()
+            }
+            Object::Native(ref native) => native.name.capacity(),
+            Object::Upvalue(_) | Object::BoundMethod(_) => 0,
+            Object::Class(ref class) => {
+                class.methods.capacity() * size_of::<(ObjRef, ObjRef)>()
+            }
+            Object::Instance(ref instance) => {
+                instance.fields.capacity() * size_of::<(ObjRef, Value)>()
+            }
+        }
+}
+
+pub struct ValueDisplay<'heap> {
+    heap: &'heap Heap,
+    value: Value,
+}
+
+impl Display for ValueDisplay<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(instance) = self.heap.as_instance(self.value) {
+            let class = self.heap.display(Value::Obj(instance.class));
+            return write!(f, "{class} instance");
+        }
+
+        if let Some(bound) = self.heap.as_bound_method(self.value) {
+            return write!(
+                f,
+                "{}",
+                self.heap.display(Value::Obj(bound.method))
+            );
+        }
+
+        match self.value {
+            Value::Obj(obj_ref) => match self.heap.get(obj_ref) {
+                Some(object) => write!(f, "{object}"),
+                None => write!(f, "{obj_ref}"),
+            },
+            value => write!(f, "{value}"),
+        }
+    }
+}
@@ -1,5 +1,8 @@
 pub(crate) mod chunk;
-pub(crate) mod compiler;
-pub(crate) mod scanner;
-pub(crate) mod value;
+pub mod compiler;
+pub mod diagnostic;
+pub(crate) mod heap;
+pub(crate) mod object;
+pub mod scanner;
+pub mod value;
 pub mod vm;
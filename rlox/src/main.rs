@@ -1,34 +1,107 @@
-use core::cmp::Ordering;
-use std::{env, process};
+use std::{env, fs, process};
 
-use rlox::vm::Vm;
+use exitcode::ExitCode;
+use rlox::{
+    diagnostic::{self, ErrorFormat},
+    scanner::{Scanner, TokenType},
+    vm::{InterpretError, Vm},
+};
+
+const USAGE: &str = "Usage: rlox [--error-format=human|json] [--tokens] [path]";
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let code = run(env::args().skip(1));
+    if code != exitcode::OK {
+        process::exit(code);
+    }
+}
+
+fn run<I>(args: I) -> ExitCode
+where
+    I: Iterator<Item = String>,
+{
+    let mut error_format = ErrorFormat::Human;
+    let mut tokens = false;
+    let mut paths = Vec::new();
+    for arg in args {
+        if arg == "--tokens" {
+            tokens = true;
+            continue;
+        }
+        match arg.strip_prefix("--error-format=") {
+            Some("human") => error_format = ErrorFormat::Human,
+            Some("json") => error_format = ErrorFormat::Json,
+            Some(_) => {
+                eprintln!("{USAGE}");
+                return exitcode::USAGE;
+            }
+            None => paths.push(arg),
+        }
+    }
 
     let mut vm = Vm::new();
 
-    match args.len().cmp(&1) {
-        Ordering::Less => {
-            eprintln!("Usage: rlox [path]");
-            process::exit(exitcode::USAGE);
+    match *paths.as_slice() {
+        [ref file_name] if tokens => {
+            let Ok(source) = fs::read_to_string(file_name) else {
+                eprintln!("Failed to open or read file {file_name}.");
+                return exitcode::IOERR;
+            };
+            return dump_tokens(&source);
         }
-        Ordering::Equal => {
+        [] if !tokens && error_format == ErrorFormat::Human => {
             if vm.repl().is_err() {
                 eprintln!("Failed to read from stdin or write to stdout.");
-                process::exit(exitcode::IOERR);
+                return exitcode::IOERR;
             }
         }
-        Ordering::Greater => {
-            #[expect(
-                clippy::indexing_slicing,
-                reason = "`Ordering::Greater` ensures `args.len()` >= 2"
-            )]
-            let file_name = &args[1];
-            if vm.run_file(file_name).is_err() {
+        [ref file_name] => {
+            let Ok(source) = fs::read_to_string(file_name) else {
                 eprintln!("Failed to open or read file {file_name}.");
-                process::exit(exitcode::IOERR);
+                return exitcode::IOERR;
+            };
+            if let Err(err) = vm.interpret(&source) {
+                match error_format {
+                    ErrorFormat::Json => {
+                        diagnostic::report_json(&err, file_name);
+                    }
+                    ErrorFormat::Human => {
+                        diagnostic::report(&err, Some(&source));
+                    }
+                }
+                return match err {
+                    InterpretError::Compile(_) => exitcode::DATAERR,
+                    _ => exitcode::SOFTWARE,
+                };
             }
         }
+        _ => {
+            eprintln!("{USAGE}");
+            return exitcode::USAGE;
+        }
+    }
+
+    exitcode::OK
+}
+
+#[expect(
+    clippy::use_debug,
+    reason = r#"
+        The token dump is a debugging aid: `Debug` names the token types and
+        escapes newlines in multi-line lexemes.
+    "#
+)]
+fn dump_tokens(source: &str) -> ExitCode {
+    let mut code = exitcode::OK;
+    for token in Scanner::new(source).with_doc_comments(true) {
+        if token.r#type == TokenType::Error {
+            code = exitcode::DATAERR;
+        }
+        let r#type = format!("{:?}", token.r#type);
+        println!(
+            "{:4}:{:<4} {type:<14} {:?}",
+            token.span.line, token.span.column, token.lexeme
+        );
     }
+    code
 }
@@ -0,0 +1,105 @@
+use core::fmt::{self, Debug, Display, Formatter};
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    chunk::Chunk,
+    value::{ObjRef, Value},
+    vm::{RuntimeError, Vm},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpvalueDescriptor {
+    pub index: usize,
+    pub is_local: bool,
+}
+
+#[derive(Debug)]
+pub struct Function {
+    pub arity: usize,
+    pub chunk: Chunk,
+    pub name: Option<String>,
+    pub upvalues: Vec<UpvalueDescriptor>,
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.name {
+            Some(ref name) => write!(f, "<fn {name}>"),
+            None => write!(f, "<script>"),
+        }
+    }
+}
+
+pub type NativeFn = dyn Fn(&mut Vm, &[Value]) -> Result<Value, RuntimeError>;
+
+pub struct Native {
+    pub name: String,
+    pub arity: usize,
+    pub function: Rc<NativeFn>,
+}
+
+impl Debug for Native {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Native")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+pub struct Closure {
+    pub function: Rc<Function>,
+    pub upvalues: Vec<ObjRef>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+#[derive(Debug)]
+pub struct Class {
+    pub name: String,
+    pub methods: HashMap<ObjRef, ObjRef>,
+}
+
+#[derive(Debug)]
+pub struct Instance {
+    pub class: ObjRef,
+    pub fields: HashMap<ObjRef, Value>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: ObjRef,
+}
+
+#[derive(Debug)]
+pub enum Object {
+    String(Rc<str>),
+    Function(Rc<Function>),
+    Native(Native),
+    Closure(Closure),
+    Upvalue(Upvalue),
+    Class(Class),
+    Instance(Instance),
+    BoundMethod(BoundMethod),
+}
+
+impl Display for Object {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::String(ref string) => write!(f, "{string}"),
+            Self::Function(ref function) => write!(f, "{function}"),
+            Self::Native(_) => write!(f, "<native fn>"),
+            Self::Closure(ref closure) => write!(f, "{}", closure.function),
+            Self::Upvalue(_) => write!(f, "upvalue"),
+            Self::Class(ref class) => write!(f, "{}", class.name),
+            Self::Instance(_) => write!(f, "instance"),
+            Self::BoundMethod(_) => write!(f, "bound method"),
+        }
+    }
+}
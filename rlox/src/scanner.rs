@@ -1,5 +1,8 @@
-#[derive(PartialEq, Eq, Clone, Copy)]
+use core::iter::FusedIterator;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[repr(u32)]
+#[non_exhaustive]
 pub enum TokenType {
     LeftParen,
     RightParen,
@@ -22,6 +25,7 @@ pub enum TokenType {
     LessEqual,
     Identifier,
     String,
+    Interpolation,
     Number,
     And,
     Class,
@@ -39,21 +43,40 @@ pub enum TokenType {
     True,
     Var,
     While,
+    DocComment,
     Error,
     Eof,
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: i32,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
 pub struct Token<'lexeme> {
     pub r#type: TokenType,
     pub lexeme: &'lexeme str,
-    pub line: i32,
+    pub span: Span,
 }
 
+#[derive(Debug)]
 pub struct Scanner<'src> {
     source: &'src str,
     start: usize,
     current: usize,
     line: i32,
+    column: usize,
+    start_line: i32,
+    start_column: usize,
+    doc_comments: bool,
+    interpolations: Vec<usize>,
+    finished: bool,
 }
 
 impl<'src> Scanner<'src> {
@@ -65,14 +88,31 @@ impl<'src> Scanner<'src> {
             start: 0,
             current: 0,
             line: 1,
+            column: 1,
+            start_line: 1,
+            start_column: 1,
+            doc_comments: false,
+            interpolations: Vec::new(),
+            finished: false,
         }
     }
 
+    #[must_use]
+    #[inline]
+    pub const fn with_doc_comments(mut self, doc_comments: bool) -> Self {
+        self.doc_comments = doc_comments;
+        self
+    }
+
     #[must_use]
     #[inline]
     pub fn scan_token(&mut self) -> Token<'src> {
-        self.skip_whitespace();
+        if let Err(message) = self.skip_whitespace() {
+            return self.error_token(message);
+        }
         self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.column;
 
         let Some(char) = self.advance() else {
             return self.make_token(TokenType::Eof);
@@ -81,13 +121,29 @@ impl<'src> Scanner<'src> {
         match char {
             '(' => self.make_token(TokenType::LeftParen),
             ')' => self.make_token(TokenType::RightParen),
-            '{' => self.make_token(TokenType::LeftBrace),
-            '}' => self.make_token(TokenType::RightBrace),
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                self.make_token(TokenType::LeftBrace)
+            }
+            '}' => match self.interpolations.last_mut() {
+                Some(&mut 0) => {
+                    self.interpolations.pop();
+                    self.string()
+                }
+                Some(depth) => {
+                    *depth -= 1;
+                    self.make_token(TokenType::RightBrace)
+                }
+                None => self.make_token(TokenType::RightBrace),
+            },
             ';' => self.make_token(TokenType::Semicolon),
             ',' => self.make_token(TokenType::Comma),
             '.' => self.make_token(TokenType::Dot),
             '-' => self.make_token(TokenType::Minus),
             '+' => self.make_token(TokenType::Plus),
+            '/' if self.peek() == Some('/') => self.doc_comment(),
             '/' => self.make_token(TokenType::Slash),
             '*' => self.make_token(TokenType::Star),
             '!' if self.matches('=') => self.make_token(TokenType::BangEqual),
@@ -102,33 +158,41 @@ impl<'src> Scanner<'src> {
             '>' => self.make_token(TokenType::Greater),
             '"' => self.string(),
             '0'..='9' => self.number(),
-            char if char.is_alphabetic() => self.identifier(),
+            char if is_identifier_start(char) => self.identifier(),
             _ => self.error_token("Unexpected character."),
         }
     }
 
     fn advance(&mut self) -> Option<char> {
-        self.current += 1;
-        self.source.chars().nth(self.current - 1)
+        let char = self.peek()?;
+        self.current += char.len_utf8();
+        if char == '\n' {
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(char)
     }
 
     fn peek(&self) -> Option<char> {
-        self.source.chars().nth(self.current)
+        self.source.get(self.current..)?.chars().next()
     }
 
     fn peek_next(&self) -> Option<char> {
-        self.source.chars().nth(self.current + 1)
+        let mut chars = self.source.get(self.current..)?.chars();
+        chars.next();
+        chars.next()
     }
 
     fn matches(&mut self, expected: char) -> bool {
-        let res = self.peek().map_or(false, |char| char == expected);
+        let res = self.peek() == Some(expected);
         if res {
             self.advance();
         }
         res
     }
 
-    fn skip_whitespace(&mut self) {
+    fn skip_whitespace(&mut self) -> Result<(), &'static str> {
         loop {
             match self.peek() {
                 Some(' ' | '\r' | '\t') => {
@@ -138,14 +202,68 @@ impl<'src> Scanner<'src> {
                     self.line += 1;
                     self.advance();
                 }
-                Some('/') if self.peek_next() == Some('/') => {
-                    while self.peek().is_some() {
-                        self.advance();
+                Some('/') => match self.peek_next() {
+                    Some('/') if self.doc_comments && self.at_doc_comment() => {
+                        return Ok(());
                     }
+                    Some('/') => self.skip_line_comment(),
+                    Some('*') => self.skip_block_comment()?,
+                    _ => return Ok(()),
+                },
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn at_doc_comment(&self) -> bool {
+        self.source.get(self.current..).is_some_and(|rest| {
+            rest.starts_with("///") && !rest.starts_with("////")
+        })
+    }
+
+    fn skip_line_comment(&mut self) {
+        while self.peek().is_some_and(|char| char != '\n') {
+            self.advance();
+        }
+    }
+
+    fn skip_block_comment(&mut self) -> Result<(), &'static str> {
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.column;
+        self.advance();
+        self.advance();
+
+        let mut depth = 1_usize;
+        while depth > 0 {
+            match (self.peek(), self.peek_next()) {
+                (None, _) => return Err("Unterminated block comment."),
+                (Some('/'), Some('*')) => {
+                    self.advance();
+                    self.advance();
+                    depth += 1;
+                }
+                (Some('*'), Some('/')) => {
+                    self.advance();
+                    self.advance();
+                    depth -= 1;
+                }
+                (Some('\n'), _) => {
+                    self.line += 1;
+                    self.advance();
+                }
+                (Some(_), _) => {
+                    self.advance();
                 }
-                _ => break,
             }
         }
+
+        Ok(())
+    }
+
+    fn doc_comment(&mut self) -> Token<'src> {
+        self.skip_line_comment();
+        self.make_token(TokenType::DocComment)
     }
 
     fn make_token(&self, r#type: TokenType) -> Token<'src> {
@@ -153,22 +271,25 @@ impl<'src> Scanner<'src> {
             return Token {
                 r#type,
                 lexeme: "",
-                line: self.line,
+                span: Span {
+                    end: self.start,
+                    ..self.span()
+                },
             };
         }
         #[expect(
             clippy::string_slice,
             reason = r#"
                 - `self.start` and `self.current` are only modified via
-                  `self.advance()`.
-                - Both values are always <= `self.source.len()`.
-                - Token creation only happens after scanning valid characters.
+                  `self.advance()`, which steps over whole UTF-8 characters.
+                - Both values are always <= `self.source.len()` and lie on
+                  character boundaries.
             "#
         )]
         Token {
             r#type,
             lexeme: &self.source[self.start..self.current],
-            line: self.line,
+            span: self.span(),
         }
     }
 
@@ -176,7 +297,16 @@ impl<'src> Scanner<'src> {
         Token {
             r#type: TokenType::Error,
             lexeme: message,
-            line: self.line,
+            span: self.span(),
+        }
+    }
+
+    const fn span(&self) -> Span {
+        Span {
+            start: self.start,
+            end: self.current,
+            line: self.start_line,
+            column: self.start_column,
         }
     }
 
@@ -186,18 +316,9 @@ impl<'src> Scanner<'src> {
         rest: &str,
         r#type: TokenType,
     ) -> TokenType {
-        #[expect(
-            clippy::string_slice,
-            reason = r#"
-                - `self.start` and `self.current` are only modified via
-                  `self.advance()`.
-                - Both values are always <= `self.source.len()`.
-            "#
-        )]
+        let begin = self.start + start;
         if self.current - self.start == start + rest.len()
-            && rest
-                == &self.source
-                    [self.start + start..self.start + start + rest.len()]
+            && self.source.get(begin..begin + rest.len()) == Some(rest)
         {
             r#type
         } else {
@@ -206,70 +327,211 @@ impl<'src> Scanner<'src> {
     }
 
     fn string(&mut self) -> Token<'src> {
-        while self.peek() != Some('"') {
-            if self.peek() == Some('\n') {
-                self.line += 1;
+        let mut error = None;
+        loop {
+            match self.peek() {
+                None => return self.error_token("Unterminated string."),
+                Some('"') => break,
+                Some('$') if self.peek_next() == Some('{') => {
+                    self.advance();
+                    self.advance();
+                    self.interpolations.push(0);
+                    return error.unwrap_or_else(|| {
+                        self.make_token(TokenType::Interpolation)
+                    });
+                }
+                Some('\\') => {
+                    let (start, column) = (self.current, self.column);
+                    if let Err(message) = self.escape() {
+                        error.get_or_insert(Token {
+                            r#type: TokenType::Error,
+                            lexeme: message,
+                            span: Span {
+                                start,
+                                end: self.current,
+                                line: self.line,
+                                column,
+                            },
+                        });
+                    }
+                }
+                Some('\n') => {
+                    self.line += 1;
+                    self.advance();
+                }
+                Some(_) => {
+                    self.advance();
+                }
             }
-            self.advance();
         }
 
-        if self.peek().is_none() {
-            self.error_token("Unterminated string.")
-        } else {
-            self.advance();
-            self.make_token(TokenType::String)
+        self.advance();
+        error.unwrap_or_else(|| self.make_token(TokenType::String))
+    }
+
+    fn escape(&mut self) -> Result<(), &'static str> {
+        self.advance();
+        match self.peek() {
+            Some('n' | 't' | '"' | '\\' | '$') => {
+                self.advance();
+                Ok(())
+            }
+            Some('u') => {
+                self.advance();
+                if !self.matches('{') {
+                    return Err("Invalid unicode escape sequence.");
+                }
+                let start = self.current;
+                while self.peek().is_some_and(|char| char.is_ascii_hexdigit()) {
+                    self.advance();
+                }
+                let digits = self.source.get(start..self.current);
+                if !self.matches('}') {
+                    return Err("Invalid unicode escape sequence.");
+                }
+                digits
+                    .filter(|digits| (1..=6).contains(&digits.len()))
+                    .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                    .and_then(char::from_u32)
+                    .map(|_| ())
+                    .ok_or("Invalid unicode escape sequence.")
+            }
+            _ => Err("Invalid escape sequence."),
         }
     }
 
     fn number(&mut self) -> Token<'src> {
-        while self.peek().is_some_and(char::is_numeric) {
+        let radix = match (
+            self.source.as_bytes().get(self.start).copied(),
+            self.peek(),
+        ) {
+            (Some(b'0'), Some('x' | 'X')) => Some(16),
+            (Some(b'0'), Some('b' | 'B')) => Some(2),
+            _ => None,
+        };
+
+        if let Some(radix) = radix {
             self.advance();
+            return match self.digits(radix, false) {
+                Ok(true) => {
+                    if !self.peek().is_some_and(unicode_ident::is_xid_continue)
+                    {
+                        return self.make_token(TokenType::Number);
+                    }
+                    while self
+                        .peek()
+                        .is_some_and(unicode_ident::is_xid_continue)
+                    {
+                        self.advance();
+                    }
+                    self.error_token("Invalid digit in number literal.")
+                }
+                Ok(false) if radix == 16 => {
+                    self.error_token("Expect hexadecimal digits after '0x'.")
+                }
+                Ok(false) => {
+                    self.error_token("Expect binary digits after '0b'.")
+                }
+                Err(message) => self.error_token(message),
+            };
+        }
+
+        if let Err(message) = self.digits(10, true) {
+            return self.error_token(message);
         }
 
         if self.peek() == Some('.')
-            && self.peek_next().is_some_and(char::is_numeric)
+            && self.peek_next().is_some_and(|char| char.is_ascii_digit())
         {
             self.advance();
 
-            while self.peek().is_some_and(char::is_numeric) {
+            if let Err(message) = self.digits(10, false) {
+                return self.error_token(message);
+            }
+        }
+
+        if matches!(self.peek(), Some('e' | 'E')) {
+            self.advance();
+            if matches!(self.peek(), Some('+' | '-')) {
                 self.advance();
             }
+
+            match self.digits(10, false) {
+                Ok(true) => {}
+                Ok(false) => {
+                    return self.error_token("Expect digits in exponent.")
+                }
+                Err(message) => return self.error_token(message),
+            }
         }
 
         self.make_token(TokenType::Number)
     }
 
+    fn digits(
+        &mut self,
+        radix: u32,
+        mut seen_digit: bool,
+    ) -> Result<bool, &'static str> {
+        loop {
+            match self.peek() {
+                Some(char) if char.is_digit(radix) => {
+                    self.advance();
+                    seen_digit = true;
+                }
+                Some('_') => {
+                    let between_digits = seen_digit
+                        && self
+                            .peek_next()
+                            .is_some_and(|char| char.is_digit(radix));
+                    self.advance();
+                    if !between_digits {
+                        return Err(
+                            "Digit separators must appear between digits.",
+                        );
+                    }
+                }
+                _ => return Ok(seen_digit),
+            }
+        }
+    }
+
     fn identifier(&mut self) -> Token<'src> {
-        while self.peek().is_some_and(char::is_alphabetic) {
+        while self.peek().is_some_and(unicode_ident::is_xid_continue) {
             self.advance();
         }
 
-        let identifier_type = match self.source.chars().nth(self.start) {
+        let identifier_type = match self.source.as_bytes().get(self.start) {
             None => {
                 return self.error_token("Unexpected character.");
             }
-            Some(char) => match char {
-                'a' => self.check_keyword(1, "nd", TokenType::And),
-                'c' => self.check_keyword(1, "lass", TokenType::Class),
-                'e' => self.check_keyword(1, "lse", TokenType::Else),
-                'i' => self.check_keyword(1, "f", TokenType::If),
-                'n' => self.check_keyword(1, "il", TokenType::Nil),
-                'o' => self.check_keyword(1, "r", TokenType::Or),
-                'p' => self.check_keyword(1, "rint", TokenType::Print),
-                'r' => self.check_keyword(1, "eturn", TokenType::Return),
-                's' => self.check_keyword(1, "uper", TokenType::Super),
-                'v' => self.check_keyword(1, "ar", TokenType::Var),
-                'w' => self.check_keyword(1, "hile", TokenType::While),
-                'f' => {
+            Some(&byte) => match byte {
+                b'a' => self.check_keyword(1, "nd", TokenType::And),
+                b'c' => self.check_keyword(1, "lass", TokenType::Class),
+                b'e' => self.check_keyword(1, "lse", TokenType::Else),
+                b'i' => self.check_keyword(1, "f", TokenType::If),
+                b'n' => self.check_keyword(1, "il", TokenType::Nil),
+                b'o' => self.check_keyword(1, "r", TokenType::Or),
+                b'p' => self.check_keyword(1, "rint", TokenType::Print),
+                b'r' => self.check_keyword(1, "eturn", TokenType::Return),
+                b's' => self.check_keyword(1, "uper", TokenType::Super),
+                b'v' => self.check_keyword(1, "ar", TokenType::Var),
+                b'w' => self.check_keyword(1, "hile", TokenType::While),
+                b'f' => {
                     if self.current - self.start > 1 {
-                        match self.source.chars().nth(self.start + 1) {
-                            Some('a') => {
+                        match self
+                            .source
+                            .as_bytes()
+                            .get(self.start + 1)
+                            .copied()
+                        {
+                            Some(b'a') => {
                                 self.check_keyword(2, "lse", TokenType::False)
                             }
-                            Some('o') => {
+                            Some(b'o') => {
                                 self.check_keyword(2, "r", TokenType::For)
                             }
-                            Some('u') => {
+                            Some(b'u') => {
                                 self.check_keyword(2, "n", TokenType::Fun)
                             }
                             _ => TokenType::Identifier,
@@ -278,13 +540,18 @@ impl<'src> Scanner<'src> {
                         TokenType::Identifier
                     }
                 }
-                't' => {
+                b't' => {
                     if self.current - self.start > 1 {
-                        match self.source.chars().nth(self.start + 1) {
-                            Some('h') => {
+                        match self
+                            .source
+                            .as_bytes()
+                            .get(self.start + 1)
+                            .copied()
+                        {
+                            Some(b'h') => {
                                 self.check_keyword(2, "is", TokenType::This)
                             }
-                            Some('r') => {
+                            Some(b'r') => {
                                 self.check_keyword(2, "ue", TokenType::True)
                             }
                             _ => TokenType::Identifier,
@@ -300,3 +567,237 @@ impl<'src> Scanner<'src> {
         self.make_token(identifier_type)
     }
 }
+
+impl<'src> Iterator for Scanner<'src> {
+    type Item = Token<'src>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let token = self.scan_token();
+        self.finished = token.r#type == TokenType::Eof;
+        Some(token)
+    }
+}
+
+impl FusedIterator for Scanner<'_> {}
+
+fn is_identifier_start(char: char) -> bool {
+    char == '_' || unicode_ident::is_xid_start(char)
+}
+
+pub(crate) fn unescape(raw: &str) -> String {
+    let mut string = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(char) = chars.next() {
+        if char != '\\' {
+            string.push(char);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => string.push('\n'),
+            Some('t') => string.push('\t'),
+            Some('u') => {
+                let digits: String = chars
+                    .by_ref()
+                    .skip(1)
+                    .take_while(|&char| char != '}')
+                    .collect();
+                if let Some(char) = u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                {
+                    string.push(char);
+                }
+            }
+            Some(char) => string.push(char),
+            None => {}
+        }
+    }
+    string
+}
+
+#[cfg(test)]
+#[expect(
+    clippy::non_ascii_literal,
+    reason = "The identifier tests are about non-ASCII source text."
+)]
+mod tests {
+    use super::{unescape, Scanner, TokenType};
+
+    fn scan(source: &str) -> Vec<(TokenType, &str)> {
+        Scanner::new(source)
+            .map(|token| (token.r#type, token.lexeme))
+            .collect()
+    }
+
+    #[test]
+    fn identifiers_in_mixed_scripts() {
+        for identifier in ["my_var", "x2", "_x", "ñandú", "变量"] {
+            assert_eq!(
+                scan(identifier),
+                [(TokenType::Identifier, identifier), (TokenType::Eof, "")]
+            );
+        }
+    }
+
+    #[test]
+    fn keywords_followed_by_non_ascii_letters_are_identifiers() {
+        for identifier in ["forñ", "classé"] {
+            assert_eq!(
+                scan(identifier),
+                [(TokenType::Identifier, identifier), (TokenType::Eof, "")]
+            );
+        }
+    }
+
+    #[test]
+    fn identifiers_cannot_start_with_digits() {
+        assert_eq!(
+            scan("2x"),
+            [
+                (TokenType::Number, "2"),
+                (TokenType::Identifier, "x"),
+                (TokenType::Eof, "")
+            ]
+        );
+    }
+
+    #[test]
+    fn escapes() {
+        let source = r#""\n\t\"\\\$\u{41}""#;
+        assert_eq!(
+            scan(source),
+            [(TokenType::String, source), (TokenType::Eof, "")]
+        );
+        assert_eq!(unescape(r#"\n\t\"\\\$\u{41}"#), "\n\t\"\\$A");
+    }
+
+    #[test]
+    fn invalid_escapes() {
+        for (source, message) in [
+            (r#""\q""#, "Invalid escape sequence."),
+            (r#""\u{}""#, "Invalid unicode escape sequence."),
+            (r#""\u{110000}""#, "Invalid unicode escape sequence."),
+            (r#""\u{41""#, "Invalid unicode escape sequence."),
+        ] {
+            assert_eq!(
+                scan(source),
+                [(TokenType::Error, message), (TokenType::Eof, "")],
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn interpolation_segments() {
+        assert_eq!(
+            scan(r#""a${b}c""#),
+            [
+                (TokenType::Interpolation, r#""a${"#),
+                (TokenType::Identifier, "b"),
+                (TokenType::String, r#"}c""#),
+                (TokenType::Eof, "")
+            ]
+        );
+        assert_eq!(
+            scan(r#""${a}${b}""#),
+            [
+                (TokenType::Interpolation, r#""${"#),
+                (TokenType::Identifier, "a"),
+                (TokenType::Interpolation, "}${"),
+                (TokenType::Identifier, "b"),
+                (TokenType::String, r#"}""#),
+                (TokenType::Eof, "")
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_interpolation() {
+        assert_eq!(
+            scan(r#""${"x${y}"}""#),
+            [
+                (TokenType::Interpolation, r#""${"#),
+                (TokenType::Interpolation, r#""x${"#),
+                (TokenType::Identifier, "y"),
+                (TokenType::String, r#"}""#),
+                (TokenType::String, r#"}""#),
+                (TokenType::Eof, "")
+            ]
+        );
+        assert_eq!(
+            scan(r#""${"}"}""#),
+            [
+                (TokenType::Interpolation, r#""${"#),
+                (TokenType::String, r#""}""#),
+                (TokenType::String, r#"}""#),
+                (TokenType::Eof, "")
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_interpolation() {
+        assert_eq!(
+            scan(r#""${1"#),
+            [
+                (TokenType::Interpolation, r#""${"#),
+                (TokenType::Number, "1"),
+                (TokenType::Eof, "")
+            ]
+        );
+        assert_eq!(
+            scan(r#""${1}"#),
+            [
+                (TokenType::Interpolation, r#""${"#),
+                (TokenType::Number, "1"),
+                (TokenType::Error, "Unterminated string."),
+                (TokenType::Eof, "")
+            ]
+        );
+    }
+
+    #[test]
+    fn number_literals() {
+        for literal in [
+            "0x1F",
+            "0XFF_FF",
+            "0b1010",
+            "1e-9",
+            "6.02E23",
+            "1_000.5e1_0",
+        ] {
+            assert_eq!(
+                scan(literal),
+                [(TokenType::Number, literal), (TokenType::Eof, "")]
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_number_literals() {
+        for (literal, message) in [
+            ("0x", "Expect hexadecimal digits after '0x'."),
+            ("0b", "Expect binary digits after '0b'."),
+            ("0b12", "Invalid digit in number literal."),
+            ("0x1G", "Invalid digit in number literal."),
+            ("1e", "Expect digits in exponent."),
+            ("1e_5", "Digit separators must appear between digits."),
+            ("1_e5", "Digit separators must appear between digits."),
+            ("1__0", "Digit separators must appear between digits."),
+            ("1_", "Digit separators must appear between digits."),
+            ("0x_1", "Digit separators must appear between digits."),
+        ] {
+            assert_eq!(
+                scan(literal).first(),
+                Some(&(TokenType::Error, message)),
+                "{literal}"
+            );
+        }
+    }
+}
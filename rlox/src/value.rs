@@ -1,39 +1,68 @@
 use core::fmt::{self, Display, Formatter};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObjRef(usize);
+
+impl ObjRef {
+    pub(crate) const fn new(idx: usize) -> Self {
+        Self(idx)
+    }
+
+    pub(crate) const fn idx(self) -> usize {
+        self.0
+    }
+}
+
+impl Display for ObjRef {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "<obj {}>", self.0)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
 pub enum Value {
     Bool(bool),
     Number(f64),
     Nil,
+    Obj(ObjRef),
 }
 
 impl Value {
-    pub const fn as_bool(&self) -> Option<bool> {
-        if let Self::Bool(value) = *self {
+    #[inline]
+    #[must_use]
+    pub const fn as_number(&self) -> Option<f64> {
+        if let Self::Number(value) = *self {
             Some(value)
         } else {
             None
         }
     }
 
-    pub const fn as_number(&self) -> Option<f64> {
-        if let Self::Number(value) = *self {
-            Some(value)
+    #[inline]
+    #[must_use]
+    pub const fn as_obj(&self) -> Option<ObjRef> {
+        if let Self::Obj(obj_ref) = *self {
+            Some(obj_ref)
         } else {
             None
         }
     }
 
+    #[inline]
+    #[must_use]
     pub const fn is_falsey(&self) -> bool {
         match *self {
             Self::Bool(value) => !value,
-            Self::Number(_) => false,
+            Self::Number(_) | Self::Obj(_) => false,
             Self::Nil => true,
         }
     }
 }
 
 impl Display for Value {
+    #[inline]
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match *self {
             Self::Bool(value) => {
@@ -41,6 +70,7 @@ impl Display for Value {
             }
             Self::Number(value) => write!(f, "{value}"),
             Self::Nil => write!(f, "nil"),
+            Self::Obj(obj_ref) => write!(f, "{obj_ref}"),
         }
     }
 }
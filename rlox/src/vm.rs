@@ -1,174 +1,967 @@
+use core::{
+    fmt::{self, Display, Formatter},
+    iter,
+};
 use std::{
-    collections::VecDeque,
+    collections::{HashMap, VecDeque},
     fs,
     io::{self, Write as _},
     path::Path,
+    rc::Rc,
+    time::Instant,
 };
 
 use thiserror::Error;
 
 use crate::{
-    chunk::{Chunk, OpCode},
-    compiler,
-    value::Value,
+    chunk::OpCode,
+    compiler::{self, ParserError},
+    diagnostic,
+    heap::Heap,
+    object::{
+        BoundMethod, Class, Closure, Function, Instance, Native, Object,
+        Upvalue,
+    },
+    scanner::Span,
+    value::{ObjRef, Value},
 };
 
 #[derive(Debug, Error)]
-enum RuntimeError {
+#[non_exhaustive]
+pub enum RuntimeError {
     #[error("Stack underflow.")]
     StackUnderflow,
     #[error("Operand must be a {0}")]
     InvalidOperand(&'static str),
+    #[error("Operands must be two numbers or two strings.")]
+    InvalidAddOperands,
+    #[error("Undefined variable '{0}'.")]
+    UndefinedVariable(String),
+    #[error("Invalid constant.")]
+    InvalidConstant,
+    #[error("Invalid instruction.")]
+    InvalidInstruction,
+    #[error("No call frame to execute.")]
+    MissingFrame,
+    #[error("Can only call functions and classes.")]
+    NotCallable,
+    #[error("Expected {expected} arguments but got {got}.")]
+    ArityMismatch { expected: usize, got: usize },
+    #[error("Stack overflow.")]
+    StackOverflow,
+    #[error("Invalid upvalue.")]
+    InvalidUpvalue,
+    #[error("Only instances have properties.")]
+    NotAnInstance,
+    #[error("Only instances have fields.")]
+    FieldOnNonInstance,
+    #[error("Undefined property '{0}'.")]
+    UndefinedProperty(String),
+    #[error("Only instances have methods.")]
+    MethodOnNonInstance,
+    #[error("Superclass must be a class.")]
+    InvalidSuperclass,
+    #[error("{0}")]
+    Native(String),
+}
+
+impl RuntimeError {
+    #[inline]
+    #[must_use]
+    pub const fn code(&self) -> &'static str {
+        match *self {
+            Self::StackUnderflow => "stack-underflow",
+            Self::InvalidOperand(_) => "invalid-operand",
+            Self::InvalidAddOperands => "invalid-add-operands",
+            Self::UndefinedVariable(_) => "undefined-variable",
+            Self::InvalidConstant => "invalid-constant",
+            Self::InvalidInstruction => "invalid-instruction",
+            Self::MissingFrame => "missing-frame",
+            Self::NotCallable => "not-callable",
+            Self::ArityMismatch { .. } => "arity-mismatch",
+            Self::StackOverflow => "stack-overflow",
+            Self::InvalidUpvalue => "invalid-upvalue",
+            Self::NotAnInstance => "not-an-instance",
+            Self::FieldOnNonInstance => "field-on-non-instance",
+            Self::UndefinedProperty(_) => "undefined-property",
+            Self::MethodOnNonInstance => "method-on-non-instance",
+            Self::InvalidSuperclass => "invalid-superclass",
+            Self::Native(_) => "native",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TraceFrame {
+    pub span: Span,
+    pub function: Option<String>,
+}
+
+impl Display for TraceFrame {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let line = self.span.line;
+        match self.function {
+            Some(ref name) => write!(f, "[line {line}] in {name}()"),
+            None => write!(f, "[line {line}] in script"),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum InterpretError {
+    #[error("{}", join_lines(.0))]
+    Compile(Vec<ParserError>),
+    #[error("{error}")]
+    Runtime {
+        error: RuntimeError,
+        trace: Vec<TraceFrame>,
+    },
 }
 
-#[derive(Debug, Default)]
+fn join_lines(errors: &[ParserError]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub type InterpretResult = Result<(), InterpretError>;
+
+const FRAMES_MAX: usize = 64;
+
+#[derive(Debug)]
+struct CallFrame {
+    closure: ObjRef,
+    function: Rc<Function>,
+    ip: usize,
+    slots: usize,
+}
+
+#[derive(Debug)]
 pub struct Vm {
     stack: VecDeque<Value>,
-    chunk: Option<Chunk>,
+    frames: Vec<CallFrame>,
+    heap: Heap,
+    globals: HashMap<ObjRef, Value>,
+    open_upvalues: Vec<ObjRef>,
+    init_string: ObjRef,
 }
 
 impl Vm {
     #[inline]
     #[must_use]
     pub fn new() -> Self {
-        Self {
+        let mut heap = Heap::new();
+        let init_string = heap.intern("init");
+        let mut vm = Self {
             stack: VecDeque::with_capacity(256),
-            chunk: None,
-        }
+            frames: Vec::with_capacity(FRAMES_MAX),
+            heap,
+            globals: HashMap::new(),
+            open_upvalues: Vec::new(),
+            init_string,
+        };
+
+        let start = Instant::now();
+        vm.define_native("clock", 0, move |_, _| {
+            Ok(Value::Number(start.elapsed().as_secs_f64()))
+        });
+
+        vm
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn as_str(&self, value: Value) -> Option<&str> {
+        self.heap.as_string(value)
+    }
+
+    #[inline]
+    pub fn new_string(&mut self, string: &str) -> Value {
+        Value::Obj(self.heap.intern(string))
+    }
+
+    #[inline]
+    pub fn define_native<F>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: Fn(&mut Self, &[Value]) -> Result<Value, RuntimeError> + 'static,
+    {
+        let native = self.heap.alloc(Object::Native(Native {
+            name: name.to_owned(),
+            arity,
+            function: Rc::new(function),
+        }));
+        let name = self.heap.intern(name);
+        self.globals.insert(name, Value::Obj(native));
     }
 
     #[inline]
     pub fn repl(&mut self) -> io::Result<()> {
+        let mut source = String::new();
         loop {
-            print!("> ");
+            print!("{}", if source.is_empty() { "> " } else { "... " });
             io::stdout().flush()?;
 
             let mut line = String::new();
-            io::stdin().read_line(&mut line)?;
+            let at_eof = io::stdin().read_line(&mut line)? == 0;
 
-            if line.is_empty() {
-                break Ok(());
+            if source.is_empty() {
+                if at_eof {
+                    break Ok(());
+                }
+                match self.interpret(&line) {
+                    Err(InterpretError::Compile(ref errors))
+                        if is_incomplete(errors, &line) =>
+                    {
+                        source = line;
+                    }
+                    Err(err) => report_repl_error(&err, &line),
+                    Ok(()) => {}
+                }
+                continue;
             }
 
-            self.interpret(line);
+            // Input spanning several lines only runs once an empty line ends
+            // it, so a later line such as `else` can still continue the
+            // statement.
+            if at_eof || line.trim().is_empty() {
+                if let Err(err) = self.interpret(&source) {
+                    report_repl_error(&err, &source);
+                }
+                source.clear();
+                if at_eof {
+                    break Ok(());
+                }
+            } else {
+                source.push_str(&line);
+            }
         }
     }
 
     #[inline]
-    pub fn run_file<P>(&mut self, path: P) -> io::Result<()>
+    pub fn run_file<P>(&mut self, path: P) -> io::Result<InterpretResult>
     where
         P: AsRef<Path>,
     {
         let contents = fs::read_to_string(path)?;
 
-        self.interpret(contents);
-
-        Ok(())
+        Ok(self.interpret(contents))
     }
 
     #[inline]
-    fn interpret<S>(&mut self, source: S)
+    pub fn interpret<S>(&mut self, source: S) -> InterpretResult
     where
         S: AsRef<str>,
     {
-        let mut chunk = Chunk::new();
+        let roots = self.roots();
+        let function =
+            compiler::compile(source.as_ref(), &mut self.heap, &roots)
+                .map_err(InterpretError::Compile)?;
 
-        if let Err(err) = compiler::compile(source.as_ref(), &mut chunk) {
-            eprintln!("{err}");
-        }
+        // Natives may re-enter `interpret`, so only the frames and stack slots
+        // pushed by this call belong to it.
+        let base_frame = self.frames.len();
+        let base_slot = self.stack.len();
 
-        self.chunk = Some(chunk);
+        let closure = self.heap.alloc(Object::Closure(Closure {
+            function,
+            upvalues: Vec::new(),
+        }));
+        self.stack.push_back(Value::Obj(closure));
+        let result = self
+            .call_value(Value::Obj(closure), 0)
+            .and_then(|()| self.run(base_frame));
 
-        let result = self.run();
+        result.map_err(|error| {
+            let trace = self.stack_trace(base_frame);
+            self.unwind(base_frame, base_slot);
+            InterpretError::Runtime { error, trace }
+        })
+    }
 
-        if let Err(err) = result {
-            eprintln!("{err}");
-            let line = self
-                .chunk
-                .as_ref()
-                .map_or(&0, |chunk| chunk.lines.first().unwrap_or(&0));
-            eprintln!("[line {line}] in script");
-        }
+    fn stack_trace(&self, base_frame: usize) -> Vec<TraceFrame> {
+        self.frames
+            .iter()
+            .skip(base_frame)
+            .rev()
+            .map(|frame| TraceFrame {
+                span: frame
+                    .function
+                    .chunk
+                    .spans
+                    .get(frame.ip.saturating_sub(1))
+                    .copied()
+                    .unwrap_or_default(),
+                function: frame.function.name.clone(),
+            })
+            .collect()
     }
 
-    fn run(&mut self) -> Result<(), RuntimeError> {
-        if let Some(chunk) = self.chunk.take() {
-            for code in chunk.codes {
-                match code {
-                    OpCode::Constant(const_idx) => {
-                        let constant = chunk
-                            .constants
-                            .get(const_idx)
-                            .ok_or(RuntimeError::StackUnderflow)?;
-                        self.stack.push_back(*constant);
-                    }
-                    OpCode::Nil => {
-                        self.stack.push_back(Value::Nil);
-                    }
-                    OpCode::True => {
-                        self.stack.push_back(Value::Bool(true));
-                    }
-                    OpCode::False => {
-                        self.stack.push_back(Value::Bool(false));
-                    }
-                    OpCode::Equal => {
-                        let a = self
-                            .stack
-                            .pop_back()
-                            .ok_or(RuntimeError::StackUnderflow)?;
-                        let b = self
+    fn unwind(&mut self, base_frame: usize, base_slot: usize) {
+        self.close_upvalues(base_slot);
+        self.frames.truncate(base_frame);
+        self.stack.truncate(base_slot);
+    }
+
+    #[expect(
+        clippy::too_many_lines,
+        reason = r#"
+            The dispatch loop handles every opcode in one place, splitting it
+            up would only scatter the instruction set.
+        "#
+    )]
+    fn run(&mut self, base_frame: usize) -> Result<(), RuntimeError> {
+        loop {
+            if self.heap.should_collect() {
+                let roots = self.roots();
+                self.heap.collect_garbage(roots);
+            }
+
+            let code = self.read_code()?;
+            match code {
+                OpCode::Constant(const_idx) => {
+                    let constant = self.read_constant(const_idx)?;
+                    self.stack.push_back(constant);
+                }
+                OpCode::Nil => {
+                    self.stack.push_back(Value::Nil);
+                }
+                OpCode::True => {
+                    self.stack.push_back(Value::Bool(true));
+                }
+                OpCode::False => {
+                    self.stack.push_back(Value::Bool(false));
+                }
+                OpCode::Equal => {
+                    let a = self
+                        .stack
+                        .pop_back()
+                        .ok_or(RuntimeError::StackUnderflow)?;
+                    let b = self
+                        .stack
+                        .pop_back()
+                        .ok_or(RuntimeError::StackUnderflow)?;
+                    self.stack.push_back(Value::Bool(a == b));
+                }
+                OpCode::Greater => {
+                    self.binary_op(|a, b| Value::Bool(a > b))?;
+                }
+                OpCode::Less => {
+                    self.binary_op(|a, b| Value::Bool(a < b))?;
+                }
+                OpCode::Add => self.add()?,
+                OpCode::Subtract => {
+                    self.binary_op(|a, b| Value::Number(a - b))?;
+                }
+                OpCode::Multiply => {
+                    self.binary_op(|a, b| Value::Number(a * b))?;
+                }
+                OpCode::Divide => {
+                    self.binary_op(|a, b| Value::Number(a / b))?;
+                }
+                OpCode::Not => {
+                    let value = self
+                        .stack
+                        .pop_back()
+                        .ok_or(RuntimeError::StackUnderflow)?
+                        .is_falsey();
+                    self.stack.push_back(Value::Bool(value));
+                }
+                OpCode::Negate => {
+                    let value = self
+                        .stack
+                        .pop_back()
+                        .ok_or(RuntimeError::StackUnderflow)?;
+                    let number = value
+                        .as_number()
+                        .ok_or(RuntimeError::InvalidOperand("number"))?;
+                    self.stack.push_back(Value::Number(-number));
+                }
+                OpCode::Print => {
+                    let value = self
+                        .stack
+                        .pop_back()
+                        .ok_or(RuntimeError::StackUnderflow)?;
+                    println!("{}", self.heap.display(value));
+                }
+                OpCode::Stringify => self.stringify()?,
+                OpCode::GetLocal(slot) => {
+                    let slot = self.frame()?.slots + slot;
+                    let value = *self
+                        .stack
+                        .get(slot)
+                        .ok_or(RuntimeError::StackUnderflow)?;
+                    self.stack.push_back(value);
+                }
+                OpCode::SetLocal(slot) => {
+                    let slot = self.frame()?.slots + slot;
+                    let value = *self
+                        .stack
+                        .back()
+                        .ok_or(RuntimeError::StackUnderflow)?;
+                    let local = self
+                        .stack
+                        .get_mut(slot)
+                        .ok_or(RuntimeError::StackUnderflow)?;
+                    *local = value;
+                }
+                OpCode::GetGlobal(const_idx) => {
+                    let name = self.read_string(const_idx)?;
+                    let value = *self.globals.get(&name).ok_or_else(|| {
+                        RuntimeError::UndefinedVariable(self.name(name))
+                    })?;
+                    self.stack.push_back(value);
+                }
+                OpCode::DefineGlobal(const_idx) => {
+                    let name = self.read_string(const_idx)?;
+                    let value = self
+                        .stack
+                        .pop_back()
+                        .ok_or(RuntimeError::StackUnderflow)?;
+                    self.globals.insert(name, value);
+                }
+                OpCode::SetGlobal(const_idx) => {
+                    let name = self.read_string(const_idx)?;
+                    let value = *self
+                        .stack
+                        .back()
+                        .ok_or(RuntimeError::StackUnderflow)?;
+                    let Some(global) = self.globals.get_mut(&name) else {
+                        return Err(RuntimeError::UndefinedVariable(
+                            self.name(name),
+                        ));
+                    };
+                    *global = value;
+                }
+                OpCode::GetUpvalue(idx) => {
+                    let upvalue = self.upvalue(idx)?;
+                    let value = match *self
+                        .heap
+                        .as_upvalue_mut(upvalue)
+                        .ok_or(RuntimeError::InvalidUpvalue)?
+                    {
+                        Upvalue::Open(slot) => *self
                             .stack
-                            .pop_back()
-                            .ok_or(RuntimeError::StackUnderflow)?;
-                        self.stack.push_back(Value::Bool(a == b));
-                    }
-                    OpCode::Greater => {
-                        self.binary_op(|a, b| Value::Bool(a > b))?;
-                    }
-                    OpCode::Less => {
-                        self.binary_op(|a, b| Value::Bool(a < b))?;
-                    }
-                    OpCode::Add => {
-                        self.binary_op(|a, b| Value::Number(a + b))?;
+                            .get(slot)
+                            .ok_or(RuntimeError::StackUnderflow)?,
+                        Upvalue::Closed(value) => value,
+                    };
+                    self.stack.push_back(value);
+                }
+                OpCode::SetUpvalue(idx) => {
+                    let upvalue = self.upvalue(idx)?;
+                    let value = self.peek(0)?;
+                    match *self
+                        .heap
+                        .as_upvalue_mut(upvalue)
+                        .ok_or(RuntimeError::InvalidUpvalue)?
+                    {
+                        Upvalue::Open(slot) => {
+                            let local = self
+                                .stack
+                                .get_mut(slot)
+                                .ok_or(RuntimeError::StackUnderflow)?;
+                            *local = value;
+                        }
+                        Upvalue::Closed(ref mut closed) => *closed = value,
                     }
-                    OpCode::Subtract => {
-                        self.binary_op(|a, b| Value::Number(a - b))?;
+                }
+                OpCode::GetProperty(const_idx) => {
+                    let instance = self.peek(0)?;
+                    let name = self.read_string(const_idx)?;
+                    let instance = self
+                        .heap
+                        .as_instance(instance)
+                        .ok_or(RuntimeError::NotAnInstance)?;
+
+                    if let Some(&value) = instance.fields.get(&name) {
+                        self.stack.pop_back();
+                        self.stack.push_back(value);
+                    } else {
+                        let class = instance.class;
+                        self.bind_method(class, name)?;
                     }
-                    OpCode::Multiply => {
-                        self.binary_op(|a, b| Value::Number(a * b))?;
+                }
+                OpCode::SetProperty(const_idx) => {
+                    let instance = self.peek(1)?;
+                    let value = self.peek(0)?;
+                    let name = self.read_string(const_idx)?;
+                    self.heap
+                        .as_instance_mut(instance)
+                        .ok_or(RuntimeError::FieldOnNonInstance)?
+                        .fields
+                        .insert(name, value);
+
+                    self.stack.pop_back();
+                    self.stack.pop_back();
+                    self.stack.push_back(value);
+                }
+                OpCode::Pop => {
+                    self.stack
+                        .pop_back()
+                        .ok_or(RuntimeError::StackUnderflow)?;
+                }
+                OpCode::Jump(offset) => self.frame_mut()?.ip += offset,
+                OpCode::JumpIfFalse(offset) => {
+                    if self
+                        .stack
+                        .back()
+                        .ok_or(RuntimeError::StackUnderflow)?
+                        .is_falsey()
+                    {
+                        self.frame_mut()?.ip += offset;
                     }
-                    OpCode::Divide => {
-                        self.binary_op(|a, b| Value::Number(a / b))?;
+                }
+                OpCode::Loop(offset) => self.frame_mut()?.ip -= offset,
+                OpCode::Call(arg_count) => {
+                    let callee = self.peek(arg_count)?;
+                    self.call_value(callee, arg_count)?;
+                }
+                OpCode::Closure(const_idx) => {
+                    let function = self.read_constant(const_idx)?;
+                    let function = self
+                        .heap
+                        .as_function(function)
+                        .map(Rc::clone)
+                        .ok_or(RuntimeError::InvalidConstant)?;
+
+                    let frame = self.frame()?;
+                    let (enclosing, slots) = (frame.closure, frame.slots);
+                    let mut upvalues =
+                        Vec::with_capacity(function.upvalues.len());
+                    for upvalue in &function.upvalues {
+                        let upvalue = if upvalue.is_local {
+                            self.capture_upvalue(slots + upvalue.index)
+                        } else {
+                            *self
+                                .heap
+                                .as_closure(enclosing)
+                                .and_then(|closure| {
+                                    closure.upvalues.get(upvalue.index)
+                                })
+                                .ok_or(RuntimeError::InvalidUpvalue)?
+                        };
+                        upvalues.push(upvalue);
                     }
-                    OpCode::Not => {
-                        let value = self
-                            .stack
-                            .pop_back()
-                            .ok_or(RuntimeError::StackUnderflow)?
-                            .is_falsey();
-                        self.stack.push_back(Value::Bool(value));
+
+                    let closure = self
+                        .heap
+                        .alloc(Object::Closure(Closure { function, upvalues }));
+                    self.stack.push_back(Value::Obj(closure));
+                }
+                OpCode::CloseUpvalue => {
+                    let last = self
+                        .stack
+                        .len()
+                        .checked_sub(1)
+                        .ok_or(RuntimeError::StackUnderflow)?;
+                    self.close_upvalues(last);
+                    self.stack.pop_back();
+                }
+                OpCode::GetSuper(const_idx) => {
+                    let name = self.read_string(const_idx)?;
+                    let superclass = self
+                        .stack
+                        .pop_back()
+                        .as_ref()
+                        .and_then(Value::as_obj)
+                        .ok_or(RuntimeError::StackUnderflow)?;
+                    self.bind_method(superclass, name)?;
+                }
+                OpCode::Invoke(const_idx, arg_count) => {
+                    let name = self.read_string(const_idx)?;
+                    self.invoke(name, arg_count)?;
+                }
+                OpCode::SuperInvoke(const_idx, arg_count) => {
+                    let name = self.read_string(const_idx)?;
+                    let superclass = self
+                        .stack
+                        .pop_back()
+                        .as_ref()
+                        .and_then(Value::as_obj)
+                        .ok_or(RuntimeError::StackUnderflow)?;
+                    self.invoke_from_class(superclass, name, arg_count)?;
+                }
+                OpCode::Class(const_idx) => {
+                    let name = self.read_string(const_idx)?;
+                    let class = self.heap.alloc(Object::Class(Class {
+                        name: self.name(name),
+                        methods: HashMap::new(),
+                    }));
+                    self.stack.push_back(Value::Obj(class));
+                }
+                OpCode::Inherit => {
+                    let methods = self
+                        .heap
+                        .as_class(self.peek(1)?)
+                        .ok_or(RuntimeError::InvalidSuperclass)?
+                        .methods
+                        .clone();
+                    self.heap
+                        .as_class_mut(self.peek(0)?)
+                        .ok_or(RuntimeError::InvalidConstant)?
+                        .methods
+                        .extend(methods);
+                    self.stack.pop_back();
+                }
+                OpCode::Method(const_idx) => {
+                    let name = self.read_string(const_idx)?;
+                    let method = self
+                        .peek(0)?
+                        .as_obj()
+                        .ok_or(RuntimeError::InvalidConstant)?;
+                    let class = self.peek(1)?;
+                    self.heap
+                        .as_class_mut(class)
+                        .ok_or(RuntimeError::InvalidConstant)?
+                        .methods
+                        .insert(name, method);
+                    self.stack.pop_back();
+                }
+                OpCode::Return => {
+                    let result = self
+                        .stack
+                        .pop_back()
+                        .ok_or(RuntimeError::StackUnderflow)?;
+                    let frame =
+                        self.frames.pop().ok_or(RuntimeError::MissingFrame)?;
+                    self.close_upvalues(frame.slots);
+                    self.stack.truncate(frame.slots);
+
+                    if self.frames.len() == base_frame {
+                        return Ok(());
                     }
-                    OpCode::Negate => {
-                        let value = self
-                            .stack
-                            .pop_back()
-                            .ok_or(RuntimeError::StackUnderflow)?;
-                        let number = value
-                            .as_number()
-                            .ok_or(RuntimeError::InvalidOperand("number"))?;
-                        self.stack.push_back(Value::Number(-number));
+
+                    self.stack.push_back(result);
+                }
+            }
+        }
+    }
+
+    fn roots(&self) -> Vec<Value> {
+        self.stack
+            .iter()
+            .copied()
+            .chain(
+                self.globals
+                    .iter()
+                    .flat_map(|(&name, &value)| [Value::Obj(name), value]),
+            )
+            .chain(self.frames.iter().map(|frame| Value::Obj(frame.closure)))
+            .chain(self.open_upvalues.iter().copied().map(Value::Obj))
+            .chain(iter::once(Value::Obj(self.init_string)))
+            .collect()
+    }
+
+    fn frame(&self) -> Result<&CallFrame, RuntimeError> {
+        self.frames.last().ok_or(RuntimeError::MissingFrame)
+    }
+
+    fn frame_mut(&mut self) -> Result<&mut CallFrame, RuntimeError> {
+        self.frames.last_mut().ok_or(RuntimeError::MissingFrame)
+    }
+
+    fn read_code(&mut self) -> Result<OpCode, RuntimeError> {
+        let frame = self.frame_mut()?;
+        let code = *frame
+            .function
+            .chunk
+            .codes
+            .get(frame.ip)
+            .ok_or(RuntimeError::InvalidInstruction)?;
+        frame.ip += 1;
+        Ok(code)
+    }
+
+    fn read_constant(&self, const_idx: usize) -> Result<Value, RuntimeError> {
+        self.frame()?
+            .function
+            .chunk
+            .constants
+            .get(const_idx)
+            .copied()
+            .ok_or(RuntimeError::InvalidConstant)
+    }
+
+    fn peek(&self, distance: usize) -> Result<Value, RuntimeError> {
+        self.stack
+            .len()
+            .checked_sub(distance + 1)
+            .and_then(|idx| self.stack.get(idx))
+            .copied()
+            .ok_or(RuntimeError::StackUnderflow)
+    }
+
+    fn call_value(
+        &mut self,
+        callee: Value,
+        arg_count: usize,
+    ) -> Result<(), RuntimeError> {
+        let callee = callee.as_obj().ok_or(RuntimeError::NotCallable)?;
+        let object = self.heap.get(callee).ok_or(RuntimeError::NotCallable)?;
+
+        match *object {
+            Object::Closure(ref closure) => {
+                let function = Rc::clone(&closure.function);
+                self.call(callee, function, arg_count)
+            }
+            Object::Class(ref class) => {
+                let initializer = class.methods.get(&self.init_string).copied();
+
+                let instance = self.heap.alloc(Object::Instance(Instance {
+                    class: callee,
+                    fields: HashMap::new(),
+                }));
+                *self.callee_slot(arg_count)? = Value::Obj(instance);
+
+                match initializer {
+                    Some(initializer) => {
+                        self.call_closure(initializer, arg_count)
                     }
-                    OpCode::Return => {
-                        let value = self
-                            .stack
-                            .pop_back()
-                            .ok_or(RuntimeError::StackUnderflow)?;
-                        println!("{value}");
-                        return Ok(());
+                    None if arg_count != 0 => {
+                        Err(RuntimeError::ArityMismatch {
+                            expected: 0,
+                            got: arg_count,
+                        })
                     }
+                    None => Ok(()),
                 }
             }
+            Object::BoundMethod(bound) => {
+                *self.callee_slot(arg_count)? = bound.receiver;
+                self.call_closure(bound.method, arg_count)
+            }
+            Object::Native(ref native) => {
+                if arg_count != native.arity {
+                    return Err(RuntimeError::ArityMismatch {
+                        expected: native.arity,
+                        got: arg_count,
+                    });
+                }
+
+                let function = Rc::clone(&native.function);
+                let callee_slot = self
+                    .stack
+                    .len()
+                    .checked_sub(arg_count + 1)
+                    .ok_or(RuntimeError::StackUnderflow)?;
+                let args: Vec<Value> =
+                    self.stack.range(callee_slot + 1..).copied().collect();
+
+                let result = function(self, &args)?;
+                self.stack.truncate(callee_slot);
+                self.stack.push_back(result);
+                Ok(())
+            }
+            _ => Err(RuntimeError::NotCallable),
+        }
+    }
+
+    fn callee_slot(
+        &mut self,
+        arg_count: usize,
+    ) -> Result<&mut Value, RuntimeError> {
+        self.stack
+            .len()
+            .checked_sub(arg_count + 1)
+            .and_then(|idx| self.stack.get_mut(idx))
+            .ok_or(RuntimeError::StackUnderflow)
+    }
+
+    fn call_closure(
+        &mut self,
+        closure: ObjRef,
+        arg_count: usize,
+    ) -> Result<(), RuntimeError> {
+        let function = self
+            .heap
+            .as_closure(closure)
+            .map(|closure| Rc::clone(&closure.function))
+            .ok_or(RuntimeError::NotCallable)?;
+        self.call(closure, function, arg_count)
+    }
+
+    fn invoke(
+        &mut self,
+        name: ObjRef,
+        arg_count: usize,
+    ) -> Result<(), RuntimeError> {
+        let receiver = self.peek(arg_count)?;
+        let instance = self
+            .heap
+            .as_instance(receiver)
+            .ok_or(RuntimeError::MethodOnNonInstance)?;
+
+        if let Some(&value) = instance.fields.get(&name) {
+            *self.callee_slot(arg_count)? = value;
+            return self.call_value(value, arg_count);
+        }
+
+        let class = instance.class;
+        self.invoke_from_class(class, name, arg_count)
+    }
+
+    fn invoke_from_class(
+        &mut self,
+        class: ObjRef,
+        name: ObjRef,
+        arg_count: usize,
+    ) -> Result<(), RuntimeError> {
+        let method = self
+            .heap
+            .as_class(Value::Obj(class))
+            .and_then(|class| class.methods.get(&name))
+            .copied()
+            .ok_or_else(|| RuntimeError::UndefinedProperty(self.name(name)))?;
+        self.call_closure(method, arg_count)
+    }
+
+    fn bind_method(
+        &mut self,
+        class: ObjRef,
+        name: ObjRef,
+    ) -> Result<(), RuntimeError> {
+        let method = self
+            .heap
+            .as_class(Value::Obj(class))
+            .and_then(|class| class.methods.get(&name))
+            .copied()
+            .ok_or_else(|| RuntimeError::UndefinedProperty(self.name(name)))?;
+
+        let receiver = self.peek(0)?;
+        let bound = self
+            .heap
+            .alloc(Object::BoundMethod(BoundMethod { receiver, method }));
+        self.stack.pop_back();
+        self.stack.push_back(Value::Obj(bound));
+        Ok(())
+    }
+
+    fn call(
+        &mut self,
+        closure: ObjRef,
+        function: Rc<Function>,
+        arg_count: usize,
+    ) -> Result<(), RuntimeError> {
+        if arg_count != function.arity {
+            return Err(RuntimeError::ArityMismatch {
+                expected: function.arity,
+                got: arg_count,
+            });
+        }
+
+        if self.frames.len() == FRAMES_MAX {
+            return Err(RuntimeError::StackOverflow);
+        }
+
+        let slots = self
+            .stack
+            .len()
+            .checked_sub(arg_count + 1)
+            .ok_or(RuntimeError::StackUnderflow)?;
+        self.frames.push(CallFrame {
+            closure,
+            function,
+            ip: 0,
+            slots,
+        });
+        Ok(())
+    }
+
+    fn upvalue(&self, idx: usize) -> Result<ObjRef, RuntimeError> {
+        self.heap
+            .as_closure(self.frame()?.closure)
+            .and_then(|closure| closure.upvalues.get(idx))
+            .copied()
+            .ok_or(RuntimeError::InvalidUpvalue)
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> ObjRef {
+        let existing = self.open_upvalues.iter().copied().find(|&upvalue| {
+            matches!(
+                self.heap.get(upvalue),
+                Some(&Object::Upvalue(Upvalue::Open(location)))
+                    if location == slot
+            )
+        });
+        if let Some(upvalue) = existing {
+            return upvalue;
         }
+
+        let upvalue = self.heap.alloc(Object::Upvalue(Upvalue::Open(slot)));
+        self.open_upvalues.push(upvalue);
+        upvalue
+    }
+
+    fn close_upvalues(&mut self, last: usize) {
+        let (heap, stack) = (&mut self.heap, &self.stack);
+        self.open_upvalues.retain(|&upvalue| {
+            let Some(upvalue) = heap.as_upvalue_mut(upvalue) else {
+                return false;
+            };
+            match *upvalue {
+                Upvalue::Open(slot) if slot >= last => {
+                    if let Some(&value) = stack.get(slot) {
+                        *upvalue = Upvalue::Closed(value);
+                    }
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+
+    fn read_string(&self, const_idx: usize) -> Result<ObjRef, RuntimeError> {
+        let constant = self.read_constant(const_idx)?;
+        self.heap
+            .as_string(constant)
+            .and(constant.as_obj())
+            .ok_or(RuntimeError::InvalidConstant)
+    }
+
+    fn name(&self, name: ObjRef) -> String {
+        self.heap
+            .as_string(Value::Obj(name))
+            .unwrap_or_default()
+            .to_owned()
+    }
+
+    fn add(&mut self) -> Result<(), RuntimeError> {
+        let b = self.stack.pop_back().ok_or(RuntimeError::StackUnderflow)?;
+        let a = self.stack.pop_back().ok_or(RuntimeError::StackUnderflow)?;
+
+        let result = if let (Value::Number(a), Value::Number(b)) = (a, b) {
+            Value::Number(a + b)
+        } else {
+            let (Some(a), Some(b)) =
+                (self.heap.as_string(a), self.heap.as_string(b))
+            else {
+                return Err(RuntimeError::InvalidAddOperands);
+            };
+            let string = format!("{a}{b}");
+            Value::Obj(self.heap.intern(&string))
+        };
+        self.stack.push_back(result);
+
+        Ok(())
+    }
+
+    fn stringify(&mut self) -> Result<(), RuntimeError> {
+        let value =
+            self.stack.pop_back().ok_or(RuntimeError::StackUnderflow)?;
+
+        let result = if self.heap.as_string(value).is_some() {
+            value
+        } else {
+            let string = self.heap.display(value).to_string();
+            Value::Obj(self.heap.intern(&string))
+        };
+        self.stack.push_back(result);
+
         Ok(())
     }
 
@@ -195,3 +988,74 @@ impl Vm {
         Ok(())
     }
 }
+
+impl Default for Vm {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn is_incomplete(errors: &[ParserError], source: &str) -> bool {
+    errors
+        .first()
+        .is_some_and(|error| error.span().end >= source.len())
+}
+
+fn report_repl_error(err: &InterpretError, source: &str) {
+    let snippet = matches!(*err, InterpretError::Compile(_)).then_some(source);
+    diagnostic::report(err, snippet);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_incomplete;
+    use crate::{compiler, heap::Heap};
+
+    fn incomplete(source: &str) -> bool {
+        let mut heap = Heap::new();
+        compiler::compile(source, &mut heap, &[])
+            .err()
+            .is_some_and(|errors| is_incomplete(&errors, source))
+    }
+
+    #[test]
+    fn unclosed_brace_is_incomplete() {
+        assert!(incomplete("fun f() {\n"));
+    }
+
+    #[test]
+    fn unclosed_paren_is_incomplete() {
+        assert!(incomplete("print (1 +\n"));
+    }
+
+    #[test]
+    fn unterminated_string_is_incomplete() {
+        assert!(incomplete("print \"multi\n"));
+    }
+
+    #[test]
+    fn unterminated_block_comment_is_incomplete() {
+        assert!(incomplete("/* comment\n"));
+    }
+
+    #[test]
+    fn missing_semicolon_at_eof_is_incomplete() {
+        assert!(incomplete("var a = 1\n"));
+    }
+
+    #[test]
+    fn error_before_eof_is_not_incomplete() {
+        assert!(!incomplete("print 1 +;\n"));
+        assert!(!incomplete("print );\nfun f() {\n"));
+    }
+
+    #[test]
+    fn if_else_split_across_lines() {
+        assert!(incomplete("if (x) {\n"));
+        assert!(incomplete("if (x) {\n  print 1;\n}\nelse {\n"));
+        assert!(!incomplete(
+            "if (x) {\n  print 1;\n}\nelse {\n  print 2;\n}\n"
+        ));
+    }
+}